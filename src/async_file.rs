@@ -0,0 +1,227 @@
+//! Parse and melt saves from tokio's async readers
+//!
+//! Text saves are melted by copying the reader to the writer as data
+//! arrives. Binary saves are melted on tokio's blocking thread pool, with
+//! the input and output streamed to and from it in chunks. Deserializing
+//! reads the rest of the input into memory first, as the underlying parsers
+//! are synchronous. That work is CPU bound, so callers that handle large
+//! saves may still want to move it off of the async runtime.
+
+use crate::{
+    file::{file_header, FileHeader, TXT_HEADER},
+    models::Hoi4Save,
+    Encoding, Hoi4Error, Hoi4ErrorKind, Hoi4File, MeltConfig, MeltedDocument,
+};
+use jomini::binary::TokenResolver;
+use serde::de::DeserializeOwned;
+use std::io::{BufWriter, Read, Write};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+};
+
+/// Size of the chunks passed between the async side and the melter
+const CHUNK_LEN: usize = 1 << 16;
+
+/// Number of chunks that may be queued in either direction
+const CHANNEL_CAPACITY: usize = 4;
+
+/// Number of emptied chunks kept to be refilled. Chunks are returned once
+/// consumed so that streaming a save doesn't allocate a buffer per chunk.
+const SPARE_CAPACITY: usize = CHANNEL_CAPACITY + 2;
+
+/// A HOI4 save read from an [`AsyncRead`] where only the header has been
+/// consumed
+#[derive(Debug)]
+pub struct Hoi4AsyncFile<R> {
+    reader: R,
+    header: [u8; TXT_HEADER.len()],
+    encoding: Encoding,
+}
+
+impl<R> Hoi4AsyncFile<R>
+where
+    R: AsyncRead + Unpin,
+{
+    pub(crate) async fn from_reader(mut reader: R) -> Result<Self, Hoi4Error> {
+        let mut header = [0u8; TXT_HEADER.len()];
+        reader.read_exact(&mut header).await?;
+        let encoding = match file_header(&header) {
+            Some((FileHeader::Text, _)) => Encoding::Plaintext,
+            Some((FileHeader::Binary, _)) => Encoding::Binary,
+            None => return Err(Hoi4Error::new(Hoi4ErrorKind::UnknownHeader)),
+        };
+
+        Ok(Hoi4AsyncFile {
+            reader,
+            header,
+            encoding,
+        })
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Reads the rest of the save into memory, including the header
+    pub async fn read_to_end(mut self) -> Result<Vec<u8>, Hoi4Error> {
+        let mut data = self.header.to_vec();
+        self.reader.read_to_end(&mut data).await?;
+        Ok(data)
+    }
+
+    pub async fn parse_save<RES>(self, resolver: RES) -> Result<Hoi4Save, Hoi4Error>
+    where
+        RES: TokenResolver,
+    {
+        self.parse(resolver).await
+    }
+
+    /// Buffers the save and deserializes it
+    pub async fn parse<T, RES>(self, resolver: RES) -> Result<T, Hoi4Error>
+    where
+        RES: TokenResolver,
+        T: DeserializeOwned,
+    {
+        let data = self.read_to_end().await?;
+        Hoi4File::from_slice(&data)?.parse(resolver)
+    }
+
+    /// Melts the save into the writer. Binary saves are melted on tokio's
+    /// blocking thread pool as the input arrives, so this must be called
+    /// from within a tokio runtime. The resolver is moved to that thread,
+    /// which is why it must be `Send + 'static`: pass an owned table or
+    /// share one with an `Arc` rather than a borrow.
+    pub async fn melt<RES, W>(
+        mut self,
+        options: impl Into<MeltConfig>,
+        resolver: RES,
+        mut output: W,
+    ) -> Result<MeltedDocument, Hoi4Error>
+    where
+        RES: TokenResolver + Send + 'static,
+        W: AsyncWrite + Unpin,
+    {
+        let doc = match self.encoding {
+            Encoding::Plaintext => {
+                output.write_all(TXT_HEADER).await?;
+                tokio::io::copy(&mut self.reader, &mut output).await?;
+                MeltedDocument::new()
+            }
+            Encoding::Binary => {
+                let (input_tx, input_rx) = mpsc::channel(CHANNEL_CAPACITY);
+                let (input_spare_tx, mut input_spare_rx) = mpsc::channel(SPARE_CAPACITY);
+                let (output_tx, mut output_rx) = mpsc::channel(CHANNEL_CAPACITY);
+                let (output_spare_tx, output_spare_rx) = mpsc::channel(SPARE_CAPACITY);
+                let input = ChannelReader {
+                    rx: input_rx,
+                    spare: input_spare_tx,
+                    chunk: self.header.to_vec(),
+                    pos: 0,
+                };
+
+                let config = options.into();
+                let melter = tokio::task::spawn_blocking(move || {
+                    let writer = ChannelWriter {
+                        tx: output_tx,
+                        spare: output_spare_rx,
+                    };
+                    let mut out = BufWriter::with_capacity(CHUNK_LEN, writer);
+                    let doc = Hoi4File::from_reader(input)?.melt(config, resolver, &mut out)?;
+                    out.flush()?;
+                    Ok::<_, Hoi4Error>(doc)
+                });
+
+                let reader = &mut self.reader;
+                let feed = async move {
+                    loop {
+                        let mut chunk = input_spare_rx.try_recv().unwrap_or_default();
+                        chunk.clear();
+                        chunk.reserve(CHUNK_LEN);
+                        let read = reader.read_buf(&mut chunk).await?;
+
+                        // The melter hangs up early when it fails
+                        if read == 0 || input_tx.send(chunk).await.is_err() {
+                            return Ok::<_, Hoi4Error>(());
+                        }
+                    }
+                };
+
+                let writer = &mut output;
+                let drain = async move {
+                    while let Some(chunk) = output_rx.recv().await {
+                        writer.write_all(&chunk).await?;
+                        let _ = output_spare_tx.try_send(chunk);
+                    }
+                    Ok::<_, Hoi4Error>(())
+                };
+
+                let (fed, drained) = tokio::join!(feed, drain);
+                fed?;
+                drained?;
+                match melter.await {
+                    Ok(doc) => doc?,
+                    Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+
+                    // Cancelled as the runtime shut down
+                    Err(e) => return Err(std::io::Error::other(e).into()),
+                }
+            }
+        };
+
+        output.flush().await?;
+        Ok(doc)
+    }
+}
+
+/// Reads the chunks sent from the async side and returns each one once it
+/// has been read
+struct ChannelReader {
+    rx: mpsc::Receiver<Vec<u8>>,
+    spare: mpsc::Sender<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => {
+                    let read = std::mem::replace(&mut self.chunk, chunk);
+                    let _ = self.spare.try_send(read);
+                    self.pos = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Sends written data to the async side in chunks that it returns once
+/// written
+struct ChannelWriter {
+    tx: mpsc::Sender<Vec<u8>>,
+    spare: mpsc::Receiver<Vec<u8>>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut chunk = self.spare.try_recv().unwrap_or_default();
+        chunk.clear();
+        chunk.extend_from_slice(buf);
+        self.tx
+            .blocking_send(chunk)
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,10 +1,19 @@
-use hoi4save::{models::Hoi4Save, BasicTokenResolver, Hoi4File};
+use hoi4save::{models::Hoi4Save, Hoi4File, TokenLoader, TokenSource, TokenTable};
 use std::env;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
-    let file_data = std::fs::read("assets/hoi4.txt").unwrap_or_default();
-    let resolver = BasicTokenResolver::from_text_lines(file_data.as_slice())?;
+    let (resolver, source) = TokenLoader::new()
+        .path("assets/hoi4.txt")
+        .load()
+        .unwrap_or_else(|e| {
+            eprintln!(
+                "warning: failed to load tokens, continuing without them: {}",
+                e
+            );
+            (TokenTable::new(), TokenSource::NotFound)
+        });
+    eprintln!("tokens loaded from: {:?}", source);
     let file = std::fs::File::open(&args[1])?;
     let mut file = Hoi4File::from_file(file)?;
     let save: Hoi4Save = file.parse_save(resolver)?;
@@ -1,11 +1,12 @@
 use hoi4save::{
     file::{Hoi4FsFileKind, Hoi4ParsedText},
-    BasicTokenResolver, Hoi4File,
+    json::JsonOptions,
+    Hoi4File, TokenLoader, TokenSource, TokenTable,
 };
 use std::{env, io::Read};
 
-fn json_to_stdout(file: &Hoi4ParsedText) {
-    let _ = file.reader().json().to_writer(std::io::stdout());
+fn json_to_stdout(file: &Hoi4ParsedText) -> Result<(), std::io::Error> {
+    file.reader().json().to_writer(std::io::stdout())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -17,16 +18,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let mut buf = Vec::new();
             x.read_to_end(&mut buf)?;
             let text = Hoi4ParsedText::from_raw(&buf)?;
-            json_to_stdout(&text);
+            json_to_stdout(&text)?;
         }
         Hoi4FsFileKind::Binary(x) => {
-            let file_data = std::fs::read("assets/hoi4.txt").unwrap_or_default();
-            let resolver = BasicTokenResolver::from_text_lines(file_data.as_slice())?;
-            let melt_options = hoi4save::MeltOptions::new().verbatim(true);
-            let mut buf = Vec::new();
-            x.melt(melt_options, resolver, &mut buf)?;
-            let text = Hoi4ParsedText::from_slice(&buf)?;
-            json_to_stdout(&text);
+            let (resolver, _) = TokenLoader::new()
+                .path("assets/hoi4.txt")
+                .load()
+                .unwrap_or_else(|e| {
+                    eprintln!(
+                        "warning: failed to load tokens, continuing without them: {}",
+                        e
+                    );
+                    (TokenTable::new(), TokenSource::NotFound)
+                });
+            let stdout = std::io::stdout();
+            x.json(JsonOptions::new(), resolver, stdout.lock())?;
         }
     }
     Ok(())
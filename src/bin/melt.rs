@@ -1,14 +1,30 @@
-use hoi4save::{BasicTokenResolver, FailedResolveStrategy, Hoi4File, MeltOptions};
+use hoi4save::{
+    FailedResolveStrategy, Hoi4File, MeltOptions, TokenLoader, TokenSource, TokenTable,
+};
 use std::{env, io::BufWriter};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
     let file = std::fs::File::open(&args[1])?;
     let mut file = Hoi4File::from_file(file)?;
-    let file_data = std::fs::read("assets/hoi4.txt").unwrap_or_default();
-    let resolver = BasicTokenResolver::from_text_lines(file_data.as_slice())?;
+    let (resolver, _) = TokenLoader::new()
+        .path("assets/hoi4.txt")
+        .load()
+        .unwrap_or_else(|e| {
+            eprintln!(
+                "warning: failed to load tokens, continuing without them: {}",
+                e
+            );
+            (TokenTable::new(), TokenSource::NotFound)
+        });
     let stdout = std::io::stdout();
-    let options = MeltOptions::new().on_failed_resolve(FailedResolveStrategy::Error);
+    // Without tokens every id is unknown, so write them as is
+    let strategy = if resolver.is_empty() {
+        FailedResolveStrategy::Stringify
+    } else {
+        FailedResolveStrategy::Error
+    };
+    let options = MeltOptions::new().on_failed_resolve(strategy);
     let mut buffer = BufWriter::new(stdout.lock());
     file.melt(options, &resolver, &mut buffer)?;
     Ok(())
@@ -0,0 +1,688 @@
+//! Traversal of parsed binary saves
+//!
+//! See [`Hoi4ParsedBinary`](crate::file::Hoi4ParsedBinary) for the entrypoint.
+
+use crate::{
+    flavor::Hoi4Flavor,
+    json::{writer_json, JsonOptions},
+    json_stream::{Json, Root, TreeSource},
+    melt::{I32Hint, KeyHints},
+    Hoi4Date, Hoi4Error, Hoi4ErrorKind,
+};
+use jomini::{
+    binary::{BinaryFlavor, TokenResolver},
+    Encoding,
+};
+use serde::{Serialize, Serializer};
+use std::borrow::Cow;
+
+/// A token from a binary save
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryToken<'a> {
+    /// Start of an object or array. Holds the index of the matching
+    /// [`BinaryToken::Close`]
+    Open(usize),
+
+    /// End of an object or array. Holds the index of the matching
+    /// [`BinaryToken::Open`]
+    Close(usize),
+
+    /// The `=` operator
+    Equal,
+
+    Bool(bool),
+    U32(u32),
+    U64(u64),
+    I32(i32),
+    I64(i64),
+
+    /// A `0x000d` value from a save that predates save version 30
+    F32(f32),
+
+    /// A `0x000d` value from a newer save, which is a fixed point number
+    /// scaled by 100000
+    Fixed(i64),
+
+    /// A `0x0167` value
+    F64(f64),
+
+    Quoted(&'a [u8]),
+    Unquoted(&'a [u8]),
+
+    /// A token that a token resolver maps to a name
+    Id(u16),
+}
+
+/// The token id of `rgb`, which is followed by an array of its channels
+pub(crate) const RGB_ID: u16 = 0x0243;
+
+/// Describes the data that follows a token id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Payload {
+    None,
+    Fixed(usize),
+    String,
+}
+
+/// Returns the payload that follows a token id. `new_save_format`
+/// determines the width of `0x000d` values.
+pub(crate) fn payload(id: u16, new_save_format: bool) -> Payload {
+    match id {
+        0x000e => Payload::Fixed(1),
+        0x000c | 0x0014 => Payload::Fixed(4),
+        0x000d if new_save_format => Payload::Fixed(8),
+        0x000d => Payload::Fixed(4),
+        0x0167 | 0x029c | 0x0317 => Payload::Fixed(8),
+        0x000f | 0x0017 => Payload::String,
+        _ => Payload::None,
+    }
+}
+
+/// The scale of the fixed point `0x000d` values of newer saves
+pub(crate) const FIXED_SCALE: i64 = 100000;
+
+/// A fixed point value as a whole number, which is how it is melted
+pub(crate) fn fixed_whole(x: i64) -> i64 {
+    x / FIXED_SCALE
+}
+
+/// A fixed point value as a float
+pub(crate) fn fixed_f64(x: i64) -> f64 {
+    x as f64 / FIXED_SCALE as f64
+}
+
+/// Decodes a token from its id and payload (sans any length prefix). Open
+/// and close tokens are returned without the index of their counterpart.
+pub(crate) fn decode_token(id: u16, data: &[u8]) -> BinaryToken<'_> {
+    fn chunk<const N: usize>(data: &[u8]) -> [u8; N] {
+        data.first_chunk::<N>().copied().unwrap_or([0; N])
+    }
+
+    let flavor = Hoi4Flavor;
+    match id {
+        0x0001 => BinaryToken::Equal,
+        0x0003 => BinaryToken::Open(0),
+        0x0004 => BinaryToken::Close(0),
+        0x000c => BinaryToken::I32(i32::from_le_bytes(chunk(data))),
+        0x000d if data.len() == 8 => BinaryToken::Fixed(i64::from_le_bytes(chunk(data))),
+        0x000d => BinaryToken::F32(flavor.visit_f32(chunk(data))),
+        0x000e => BinaryToken::Bool(chunk::<1>(data)[0] != 0),
+        0x000f => BinaryToken::Quoted(data),
+        0x0014 => BinaryToken::U32(u32::from_le_bytes(chunk(data))),
+        0x0017 => BinaryToken::Unquoted(data),
+        0x0167 => BinaryToken::F64(flavor.visit_f64(chunk(data))),
+        0x029c => BinaryToken::U64(u64::from_le_bytes(chunk(data))),
+        0x0317 => BinaryToken::I64(i64::from_le_bytes(chunk(data))),
+        id => BinaryToken::Id(id),
+    }
+}
+
+/// Decodes binary tokens from a slice
+#[derive(Debug)]
+pub(crate) struct Lexer<'a> {
+    data: &'a [u8],
+    len: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Lexer {
+            data,
+            len: data.len(),
+        }
+    }
+
+    /// The number of bytes that have been read
+    pub(crate) fn position(&self) -> usize {
+        self.len - self.data.len()
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], Hoi4Error> {
+        let (head, rest) = self.data.split_at_checked(len).ok_or(Hoi4ErrorKind::Eof)?;
+        self.data = rest;
+        Ok(head)
+    }
+
+    /// Reads the next token. See [`decode_token`].
+    pub(crate) fn read_token(
+        &mut self,
+        new_save_format: bool,
+    ) -> Result<Option<BinaryToken<'a>>, Hoi4Error> {
+        if self.data.is_empty() {
+            return Ok(None);
+        }
+
+        let id = self.read_bytes(2)?;
+        let id = u16::from_le_bytes([id[0], id[1]]);
+        let data = match payload(id, new_save_format) {
+            Payload::None => &[],
+            Payload::Fixed(len) => self.read_bytes(len)?,
+            Payload::String => {
+                let len = self.read_bytes(2)?;
+                self.read_bytes(usize::from(u16::from_le_bytes([len[0], len[1]])))?
+            }
+        };
+
+        Ok(Some(decode_token(id, data)))
+    }
+}
+
+/// Parses binary data (sans header) into a flat list of tokens where open
+/// and close tokens reference each other
+pub(crate) fn parse_tokens<'a, R>(
+    data: &'a [u8],
+    resolver: &R,
+) -> Result<Vec<BinaryToken<'a>>, Hoi4Error>
+where
+    R: TokenResolver,
+{
+    let mut lexer = Lexer::new(data);
+    let mut tokens = Vec::with_capacity(data.len() / 5);
+    let mut stack = Vec::new();
+    let mut save_version_id = false;
+    let mut new_save_format = false;
+
+    loop {
+        let offset = lexer.position();
+        let Some(token) = lexer.read_token(new_save_format)? else {
+            break;
+        };
+
+        match token {
+            BinaryToken::Open(_) => {
+                stack.push(tokens.len());
+                tokens.push(token);
+            }
+            BinaryToken::Close(_) => {
+                let Some(start) = stack.pop() else {
+                    return Err(Hoi4ErrorKind::UnbalancedContainers { offset }.into());
+                };
+                let end = tokens.len();
+                tokens[start] = BinaryToken::Open(end);
+                tokens.push(BinaryToken::Close(start));
+            }
+            BinaryToken::I32(x) if save_version_id => {
+                new_save_format = x >= 30;
+                tokens.push(token);
+            }
+            BinaryToken::Id(id) => {
+                if let Some(name) = resolver.resolve(id) {
+                    save_version_id = name == "save_version";
+                }
+                tokens.push(token);
+            }
+            _ => tokens.push(token),
+        }
+    }
+
+    if stack.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(Hoi4ErrorKind::Eof.into())
+    }
+}
+
+/// Index of the element that follows the element at the given index
+fn next_index(tokens: &[BinaryToken], idx: usize) -> usize {
+    match tokens[idx] {
+        BinaryToken::Open(end) => end + 1,
+        _ => idx + 1,
+    }
+}
+
+/// Index of the value that follows the value at the given index. A header,
+/// like `rgb`, and the container it precedes are a single value.
+pub(crate) fn next_value_index(tokens: &[BinaryToken], idx: usize) -> usize {
+    match (tokens[idx], tokens.get(idx + 1)) {
+        (BinaryToken::Id(RGB_ID), Some(BinaryToken::Open(end))) => end + 1,
+        _ => next_index(tokens, idx),
+    }
+}
+
+pub(crate) fn resolve_str<'doc, 'data, R>(
+    resolver: &'doc R,
+    token: &BinaryToken<'data>,
+) -> Option<Cow<'doc, str>>
+where
+    R: TokenResolver,
+    'data: 'doc,
+{
+    let result = match *token {
+        BinaryToken::Quoted(x) | BinaryToken::Unquoted(x) => Hoi4Flavor.decode(x),
+        BinaryToken::Id(id) => match resolver.resolve(id) {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Owned(format!("__unknown_0x{:x}", id)),
+        },
+        BinaryToken::Bool(x) => Cow::Borrowed(if x { "yes" } else { "no" }),
+        BinaryToken::U32(x) => Cow::Owned(x.to_string()),
+        BinaryToken::U64(x) => Cow::Owned(x.to_string()),
+        BinaryToken::I32(x) => Cow::Owned(x.to_string()),
+        BinaryToken::I64(x) => Cow::Owned(x.to_string()),
+        BinaryToken::F32(x) => Cow::Owned(x.to_string()),
+        BinaryToken::Fixed(x) => Cow::Owned(fixed_whole(x).to_string()),
+        BinaryToken::F64(x) => Cow::Owned(x.to_string()),
+        BinaryToken::Open(_) | BinaryToken::Close(_) | BinaryToken::Equal => return None,
+    };
+
+    Some(result)
+}
+
+/// Reads the key of a field
+pub struct ScalarReader<'doc, 'data, R> {
+    tokens: &'doc [BinaryToken<'data>],
+    resolver: &'doc R,
+    idx: usize,
+}
+
+impl<R> Clone for ScalarReader<'_, '_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for ScalarReader<'_, '_, R> {}
+
+impl<'doc, 'data, R> ScalarReader<'doc, 'data, R>
+where
+    R: TokenResolver,
+{
+    /// The underlying token
+    pub fn token(&self) -> &BinaryToken<'data> {
+        &self.tokens[self.idx]
+    }
+
+    /// Returns the key as a string. Token ids are resolved with the token
+    /// resolver, and unknown tokens are stringified as `__unknown_0x...`
+    pub fn read_str(&self) -> Cow<'doc, str> {
+        resolve_str(self.resolver, self.token()).unwrap_or(Cow::Borrowed(""))
+    }
+}
+
+/// Reads a value, which may be a scalar, object, or array
+pub struct ValueReader<'doc, 'data, R> {
+    tokens: &'doc [BinaryToken<'data>],
+    resolver: &'doc R,
+    idx: usize,
+    key: Option<usize>,
+}
+
+impl<R> Clone for ValueReader<'_, '_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for ValueReader<'_, '_, R> {}
+
+impl<'doc, 'data, R> ValueReader<'doc, 'data, R>
+where
+    R: TokenResolver,
+{
+    /// The underlying token
+    pub fn token(&self) -> &BinaryToken<'data> {
+        &self.tokens[self.idx]
+    }
+
+    /// Returns the value as a string. Returns `None` for objects and arrays.
+    pub fn read_str(&self) -> Option<Cow<'doc, str>> {
+        resolve_str(self.resolver, self.token())
+    }
+
+    /// Returns the value as an integer
+    pub fn read_i64(&self) -> Option<i64> {
+        match *self.token() {
+            BinaryToken::I32(x) => Some(i64::from(x)),
+            BinaryToken::U32(x) => Some(i64::from(x)),
+            BinaryToken::I64(x) => Some(x),
+            BinaryToken::U64(x) => i64::try_from(x).ok(),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a float
+    pub fn read_f64(&self) -> Option<f64> {
+        match *self.token() {
+            BinaryToken::F32(x) => Some(f64::from(x)),
+            BinaryToken::F64(x) => Some(x),
+            BinaryToken::Fixed(x) => Some(fixed_f64(x)),
+            _ => self.read_i64().map(|x| x as f64),
+        }
+    }
+
+    /// Returns the value as a boolean
+    pub fn read_bool(&self) -> Option<bool> {
+        match *self.token() {
+            BinaryToken::Bool(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a date
+    pub fn read_date(&self) -> Option<Hoi4Date> {
+        match *self.token() {
+            BinaryToken::I32(x) => Hoi4Date::from_binary(x),
+            _ => None,
+        }
+    }
+
+    /// Interprets the value as an object. Returns `None` if the value is not
+    /// a container or is a container of values without keys.
+    pub fn read_object(&self) -> Option<ObjectReader<'doc, 'data, R>> {
+        let BinaryToken::Open(end) = *self.token() else {
+            return None;
+        };
+
+        let start = self.idx + 1;
+        let object_like =
+            start == end || matches!(self.tokens.get(start + 1), Some(BinaryToken::Equal));
+        object_like.then_some(ObjectReader {
+            tokens: self.tokens,
+            resolver: self.resolver,
+            start,
+            end,
+        })
+    }
+
+    /// Interprets the value as an array. Returns `None` if the value is not
+    /// a container.
+    pub fn read_array(&self) -> Option<ArrayReader<'doc, 'data, R>> {
+        let BinaryToken::Open(end) = *self.token() else {
+            return None;
+        };
+
+        Some(ArrayReader {
+            tokens: self.tokens,
+            resolver: self.resolver,
+            start: self.idx + 1,
+            end,
+        })
+    }
+
+    /// Converts the value to JSON
+    pub fn json(&self) -> JsonBuilder<'doc, 'data, R> {
+        JsonBuilder {
+            value: JsonValue::Value(*self),
+            options: JsonOptions::default(),
+        }
+    }
+
+    fn hint(&self, hints: &KeyHints) -> I32Hint {
+        self.key
+            .and_then(|key| match self.tokens[key] {
+                BinaryToken::Id(id) => self.resolver.resolve(id),
+                _ => None,
+            })
+            .map_or(I32Hint::Heuristic, |key| hints.hint(key))
+    }
+}
+
+/// Iterates over the fields of an object
+pub struct ObjectReader<'doc, 'data, R> {
+    tokens: &'doc [BinaryToken<'data>],
+    resolver: &'doc R,
+    start: usize,
+    end: usize,
+}
+
+impl<R> Clone for ObjectReader<'_, '_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for ObjectReader<'_, '_, R> {}
+
+impl<'doc, 'data, R> ObjectReader<'doc, 'data, R>
+where
+    R: TokenResolver,
+{
+    pub(crate) fn root(tokens: &'doc [BinaryToken<'data>], resolver: &'doc R) -> Self {
+        ObjectReader {
+            tokens,
+            resolver,
+            start: 0,
+            end: tokens.len(),
+        }
+    }
+
+    /// Iterates over the key value pairs of the object. Values that are not
+    /// part of a key value pair are skipped.
+    pub fn fields(&self) -> FieldsIter<'doc, 'data, R> {
+        FieldsIter {
+            reader: *self,
+            idx: self.start,
+        }
+    }
+
+    /// Converts the object to JSON
+    pub fn json(&self) -> JsonBuilder<'doc, 'data, R> {
+        JsonBuilder {
+            value: JsonValue::Object(*self),
+            options: JsonOptions::default(),
+        }
+    }
+}
+
+/// Iterator over the fields of an [`ObjectReader`]
+pub struct FieldsIter<'doc, 'data, R> {
+    reader: ObjectReader<'doc, 'data, R>,
+    idx: usize,
+}
+
+impl<'doc, 'data, R> Iterator for FieldsIter<'doc, 'data, R>
+where
+    R: TokenResolver,
+{
+    type Item = (ScalarReader<'doc, 'data, R>, ValueReader<'doc, 'data, R>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tokens = self.reader.tokens;
+        while self.idx < self.reader.end {
+            let key = self.idx;
+            let value = key + 2;
+            if !matches!(tokens.get(key + 1), Some(BinaryToken::Equal)) || value >= self.reader.end
+            {
+                self.idx = next_index(tokens, key);
+                continue;
+            }
+
+            self.idx = next_value_index(tokens, value);
+            let resolver = self.reader.resolver;
+            return Some((
+                ScalarReader {
+                    tokens,
+                    resolver,
+                    idx: key,
+                },
+                ValueReader {
+                    tokens,
+                    resolver,
+                    idx: value,
+                    key: Some(key),
+                },
+            ));
+        }
+
+        None
+    }
+}
+
+/// Iterates over the values of an array
+pub struct ArrayReader<'doc, 'data, R> {
+    tokens: &'doc [BinaryToken<'data>],
+    resolver: &'doc R,
+    start: usize,
+    end: usize,
+}
+
+impl<R> Clone for ArrayReader<'_, '_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for ArrayReader<'_, '_, R> {}
+
+impl<'doc, 'data, R> ArrayReader<'doc, 'data, R>
+where
+    R: TokenResolver,
+{
+    /// Iterates over the values of the array. Operators of any key value
+    /// pairs within the array are yielded too.
+    pub fn values(&self) -> ValuesIter<'doc, 'data, R> {
+        ValuesIter {
+            reader: *self,
+            idx: self.start,
+        }
+    }
+
+    /// Converts the array to JSON
+    pub fn json(&self) -> JsonBuilder<'doc, 'data, R> {
+        JsonBuilder {
+            value: JsonValue::Array(*self),
+            options: JsonOptions::default(),
+        }
+    }
+}
+
+/// Iterator over the values of an [`ArrayReader`]
+pub struct ValuesIter<'doc, 'data, R> {
+    reader: ArrayReader<'doc, 'data, R>,
+    idx: usize,
+}
+
+impl<'doc, 'data, R> Iterator for ValuesIter<'doc, 'data, R>
+where
+    R: TokenResolver,
+{
+    type Item = ValueReader<'doc, 'data, R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx >= self.reader.end {
+            return None;
+        }
+
+        let idx = self.idx;
+        self.idx = next_value_index(self.reader.tokens, idx);
+        Some(ValueReader {
+            tokens: self.reader.tokens,
+            resolver: self.reader.resolver,
+            idx,
+            key: None,
+        })
+    }
+}
+
+enum JsonValue<'doc, 'data, R> {
+    Object(ObjectReader<'doc, 'data, R>),
+    Array(ArrayReader<'doc, 'data, R>),
+    Value(ValueReader<'doc, 'data, R>),
+}
+
+/// Creates JSON from a reader
+pub struct JsonBuilder<'doc, 'data, R> {
+    value: JsonValue<'doc, 'data, R>,
+    options: JsonOptions,
+}
+
+impl<R> JsonBuilder<'_, '_, R>
+where
+    R: TokenResolver,
+{
+    pub fn with_options(self, options: JsonOptions) -> Self {
+        JsonBuilder { options, ..self }
+    }
+
+    pub fn to_writer<W>(self, writer: W) -> Result<(), std::io::Error>
+    where
+        W: std::io::Write,
+    {
+        writer_json(writer, self.options.pretty(), &self)
+    }
+
+    pub fn to_vec(self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        // Writing to a vec shouldn't fail and we control the type being
+        // serialized, so an error is a programmer error
+        if let Err(e) = self.to_writer(&mut out) {
+            panic!("failed to serialize json to vector: {}", e)
+        }
+        out
+    }
+
+    #[allow(clippy::inherent_to_string)]
+    pub fn to_string(self) -> String {
+        // serde_json doesn't generate invalid utf-8
+        String::from_utf8(self.to_vec()).unwrap_or_default()
+    }
+}
+
+impl<R> Serialize for JsonBuilder<'_, '_, R>
+where
+    R: TokenResolver,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let (source, root) = match self.value {
+            JsonValue::Object(x) => (
+                TreeSource::range(x.tokens, x.resolver, x.start, x.end),
+                Root::Object,
+            ),
+            JsonValue::Array(x) => (
+                TreeSource::range(x.tokens, x.resolver, x.start, x.end),
+                Root::Array,
+            ),
+            JsonValue::Value(x) => (
+                TreeSource::value(x.tokens, x.resolver, x.idx),
+                Root::Value(x.hint(self.options.hints())),
+            ),
+        };
+
+        Json::new(source, &self.options)
+            .root(root)
+            .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::DuplicateKeyMode;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_json_duplicate_keys() {
+        // a={ b=1 b=2 } c={ { d=3 } }
+        let data = [
+            0x00, 0x20, 0x01, 0x00, 0x03, 0x00, //
+            0x01, 0x20, 0x01, 0x00, 0x0c, 0x00, 0x01, 0x00, 0x00, 0x00, //
+            0x01, 0x20, 0x01, 0x00, 0x0c, 0x00, 0x02, 0x00, 0x00, 0x00, //
+            0x04, 0x00, //
+            0x02, 0x20, 0x01, 0x00, 0x03, 0x00, 0x03, 0x00, //
+            0x03, 0x20, 0x01, 0x00, 0x0c, 0x00, 0x03, 0x00, 0x00, 0x00, //
+            0x04, 0x00, 0x04, 0x00,
+        ];
+
+        let resolver: HashMap<u16, &str> =
+            HashMap::from([(0x2000, "a"), (0x2001, "b"), (0x2002, "c"), (0x2003, "d")]);
+        let tokens = parse_tokens(&data, &resolver).unwrap();
+        let reader = ObjectReader::root(&tokens, &resolver);
+
+        let preserve = reader.json().to_string();
+        assert_eq!(preserve, r#"{"a":{"b":1,"b":2},"c":[{"d":3}]}"#);
+
+        let options = JsonOptions::new().with_duplicate_keys(DuplicateKeyMode::Group);
+        let group = reader.json().with_options(options).to_string();
+        assert_eq!(group, r#"{"a":{"b":[1,2]},"c":[{"d":3}]}"#);
+
+        let options = JsonOptions::new().with_duplicate_keys(DuplicateKeyMode::KeyValuePairs);
+        let pairs = reader.json().with_options(options).to_string();
+        assert_eq!(
+            pairs,
+            r#"{"type":"obj","val":[["a",{"type":"obj","val":[["b",1],["b",2]]}],["c",{"type":"array","val":[{"type":"obj","val":[["d",3]]}]}]]}"#
+        );
+    }
+}
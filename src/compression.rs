@@ -0,0 +1,171 @@
+//! Unwraps saves that have been compressed or archived
+
+use crate::{Container, Hoi4Error, Hoi4ErrorKind};
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use rawzip::{CompressionMethod, ZipArchive};
+use std::io::{self, Chain, Cursor, Read};
+
+/// Number of leading bytes needed to detect the container
+const DETECT_LEN: usize = 4;
+
+/// The default maximum size of a decompressed save. Uncompressed saves are
+/// a few hundred megabytes, so anything larger is likely a zip bomb.
+pub const DEFAULT_DECOMPRESSED_LIMIT: u64 = 1 << 30;
+
+pub(crate) fn detect(data: &[u8]) -> Container {
+    match data {
+        [b'P', b'K', 0x03, 0x04, ..] => Container::Zip,
+        [0x1f, 0x8b, ..] => Container::Gzip,
+        [0x78, flags, ..] if (0x7800 | u16::from(*flags)) % 31 == 0 => Container::Zlib,
+        _ => Container::Raw,
+    }
+}
+
+/// Writes the save contained in `data` to `out`
+pub(crate) fn decompress_slice(
+    data: &[u8],
+    container: Container,
+    out: &mut Vec<u8>,
+) -> Result<(), Hoi4Error> {
+    match container {
+        Container::Raw => out.extend_from_slice(data),
+        Container::Zip => unzip(data, out, DEFAULT_DECOMPRESSED_LIMIT)?,
+        Container::Gzip => read_limited(GzDecoder::new(data), out, DEFAULT_DECOMPRESSED_LIMIT)?,
+        Container::Zlib => read_limited(ZlibDecoder::new(data), out, DEFAULT_DECOMPRESSED_LIMIT)?,
+    }
+
+    Ok(())
+}
+
+/// Reads the decompressed data to the end, failing once it exceeds `limit`
+fn read_limited<R: Read>(reader: R, out: &mut Vec<u8>, limit: u64) -> Result<(), Hoi4Error> {
+    let read = reader.take(limit.saturating_add(1)).read_to_end(out)?;
+    if read as u64 > limit {
+        return Err(Hoi4ErrorKind::DecompressedSizeLimit { limit }.into());
+    }
+
+    Ok(())
+}
+
+/// Extracts the first file in the zip archive
+fn unzip(data: &[u8], out: &mut Vec<u8>, limit: u64) -> Result<(), Hoi4Error> {
+    let archive = ZipArchive::from_slice(data)?;
+    let mut entries = archive.entries();
+    let entry = loop {
+        match entries.next_entry()? {
+            Some(entry) if entry.is_dir() => continue,
+            Some(entry) => break entry,
+            None => return Err(Hoi4ErrorKind::ZipMissingSave.into()),
+        }
+    };
+
+    let method = entry.compression_method();
+    let zip_entry = archive.get_entry(entry.wayfinder())?;
+    match method {
+        CompressionMethod::Store => {
+            read_limited(zip_entry.verifying_reader(zip_entry.data()), out, limit)?;
+        }
+        CompressionMethod::Deflate => {
+            let inflater = DeflateDecoder::new(zip_entry.data());
+            read_limited(zip_entry.verifying_reader(inflater), out, limit)?;
+        }
+        method => {
+            return Err(Hoi4ErrorKind::ZipUnsupportedCompression {
+                method: method.as_id().as_u16(),
+            }
+            .into())
+        }
+    }
+
+    Ok(())
+}
+
+type Peeked<R> = Chain<Cursor<Vec<u8>>, R>;
+
+#[derive(Debug)]
+enum DecompressorKind<R> {
+    Raw(Peeked<R>),
+    Zip(Cursor<Vec<u8>>),
+    Gzip(GzDecoder<Peeked<R>>),
+    Zlib(ZlibDecoder<Peeked<R>>),
+}
+
+/// A reader that yields the save inside of a container
+///
+/// Gzip and zlib data is decompressed as it is read. A zip archive records
+/// the location of its files at the end of the archive, so the input is read
+/// fully into memory before the save is extracted.
+///
+/// Reading fails once the decompressed data exceeds the limit, which is
+/// [`DEFAULT_DECOMPRESSED_LIMIT`] unless set with
+/// [`Decompressor::with_limit`].
+#[derive(Debug)]
+pub struct Decompressor<R> {
+    kind: DecompressorKind<R>,
+    container: Container,
+    limit: u64,
+    read: u64,
+}
+
+impl<R: Read> Decompressor<R> {
+    /// Detects the container from the first few bytes of the reader
+    pub fn new(reader: R) -> Result<Self, Hoi4Error> {
+        Self::with_limit(reader, DEFAULT_DECOMPRESSED_LIMIT)
+    }
+
+    /// Detects the container from the first few bytes of the reader and
+    /// limits the decompressed size of a compressed save to `limit` bytes.
+    /// Uncompressed data is not limited.
+    pub fn with_limit(mut reader: R, limit: u64) -> Result<Self, Hoi4Error> {
+        let mut head = Vec::with_capacity(DETECT_LEN);
+        reader
+            .by_ref()
+            .take(DETECT_LEN as u64)
+            .read_to_end(&mut head)?;
+        let container = detect(&head);
+        let mut peeked = Cursor::new(head).chain(reader);
+        let kind = match container {
+            Container::Raw => DecompressorKind::Raw(peeked),
+            Container::Gzip => DecompressorKind::Gzip(GzDecoder::new(peeked)),
+            Container::Zlib => DecompressorKind::Zlib(ZlibDecoder::new(peeked)),
+            Container::Zip => {
+                let mut data = Vec::new();
+                peeked.read_to_end(&mut data)?;
+                let mut out = Vec::new();
+                unzip(&data, &mut out, limit)?;
+                DecompressorKind::Zip(Cursor::new(out))
+            }
+        };
+
+        Ok(Decompressor {
+            kind,
+            container,
+            limit,
+            read: 0,
+        })
+    }
+
+    /// The container that was detected
+    pub fn container(&self) -> Container {
+        self.container
+    }
+}
+
+impl<R: Read> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = match &mut self.kind {
+            DecompressorKind::Raw(reader) => return reader.read(buf),
+            DecompressorKind::Zip(reader) => reader.read(buf)?,
+            DecompressorKind::Gzip(reader) => reader.read(buf)?,
+            DecompressorKind::Zlib(reader) => reader.read(buf)?,
+        };
+
+        self.read += read as u64;
+        if self.read > self.limit {
+            let kind = Hoi4ErrorKind::DecompressedSizeLimit { limit: self.limit };
+            return Err(io::Error::new(io::ErrorKind::InvalidData, kind.to_string()));
+        }
+
+        Ok(read)
+    }
+}
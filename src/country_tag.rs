@@ -71,6 +71,23 @@ impl CountryTag {
         debug_assert!(std::str::from_utf8(&self.0).is_ok());
         unsafe { std::str::from_utf8_unchecked(&self.0) }
     }
+
+    /// Returns true if the tag is one that the game dynamically assigns to
+    /// countries created mid-game (eg: civil war revolters, collaboration
+    /// governments), which take the form of `D` followed by two digits.
+    ///
+    /// Use [`Hoi4Save::original_tag`](crate::models::Hoi4Save::original_tag)
+    /// to map a dynamic tag back to the country it represents.
+    ///
+    /// ```
+    /// use hoi4save::CountryTag;
+    /// assert!(CountryTag::create(b"D01")?.is_dynamic());
+    /// assert!(!CountryTag::create(b"DEN")?.is_dynamic());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn is_dynamic(&self) -> bool {
+        matches!(self.0, [b'D', a, b] if a.is_ascii_digit() && b.is_ascii_digit())
+    }
 }
 
 #[inline]
@@ -0,0 +1,105 @@
+use serde::{de, Deserialize, Deserializer};
+use std::fmt;
+
+/// An element of a color sequence: either a single channel, a nested group of
+/// channels (what follows an `rgb` header), or something to ignore.
+enum Channel {
+    Value(f64),
+    Group(Vec<f64>),
+    Other,
+}
+
+impl<'de> Deserialize<'de> for Channel {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ChannelVisitor;
+
+        impl<'de> de::Visitor<'de> for ChannelVisitor {
+            type Value = Channel;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a color channel")
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+                Ok(Channel::Value(v))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(Channel::Value(v as f64))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(Channel::Value(v as f64))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(v.parse().map_or(Channel::Other, Channel::Value))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let mut values = Vec::new();
+                while let Some(channel) = seq.next_element::<Channel>()? {
+                    if let Channel::Value(x) = channel {
+                        values.push(x);
+                    }
+                }
+                Ok(Channel::Group(values))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                while map
+                    .next_entry::<de::IgnoredAny, de::IgnoredAny>()?
+                    .is_some()
+                {}
+                Ok(Channel::Other)
+            }
+        }
+
+        deserializer.deserialize_any(ChannelVisitor)
+    }
+}
+
+/// Deserializes the channels of a color. Both `color = { 1 2 3 }` and
+/// `color = rgb { 1 2 3 }` are accepted.
+pub fn deserialize_color<'de, D>(deserializer: D) -> Result<Vec<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct Visitor;
+
+    impl<'de> de::Visitor<'de> for Visitor {
+        type Value = Vec<f64>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a sequence of color channels")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: de::SeqAccess<'de>,
+        {
+            let mut values = Vec::new();
+            while let Some(channel) = seq.next_element()? {
+                match channel {
+                    Channel::Value(x) => values.push(x),
+
+                    // The group after a header is the last element
+                    Channel::Group(x) => return Ok(x),
+                    Channel::Other => {}
+                }
+            }
+            Ok(values)
+        }
+    }
+
+    deserializer.deserialize_seq(Visitor)
+}
@@ -1,5 +1,7 @@
+mod color;
 mod hashmap_f64;
 mod vec_pair;
 
+pub use color::*;
 pub use hashmap_f64::*;
 pub use vec_pair::*;
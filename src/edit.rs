@@ -0,0 +1,164 @@
+use crate::{file::Hoi4ParsedText, Hoi4Error, Hoi4ErrorKind};
+use jomini::{
+    text::{ObjectReader, ValueReader},
+    Scalar, TextToken, Utf8Encoding,
+};
+use std::{io::Write, ops::Range};
+
+/// Records replacements of scalar values in a parsed text save and writes
+/// the save back out. Everything outside of the replaced values, including
+/// whitespace, comments and the header, is written byte for byte.
+///
+/// Values are addressed by a dot separated path of keys, where a numeric
+/// segment indexes into an array (eg: `countries.FRA.stability` or
+/// `countries.D01.color.0`). When a key occurs more than once in an
+/// object, the first occurrence that contains the rest of the path is
+/// edited.
+///
+/// ```rust
+/// use hoi4save::file::Hoi4ParsedText;
+/// let data = b"HOI4txt\ncountries={\n\tFRA={ stability=0.5 } # vichy\n}\n";
+/// let parsed = Hoi4ParsedText::from_slice(data)?;
+/// let mut editor = parsed.editor();
+/// editor.set("countries.FRA.stability", "0.75")?;
+/// let mut out = Vec::new();
+/// editor.write(&mut out)?;
+/// assert_eq!(&out, b"HOI4txt\ncountries={\n\tFRA={ stability=0.75 } # vichy\n}\n");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Hoi4TextEditor<'p, 'a> {
+    parsed: &'p Hoi4ParsedText<'a>,
+    edits: Vec<(Range<usize>, Vec<u8>)>,
+}
+
+impl<'p, 'a> Hoi4TextEditor<'p, 'a> {
+    pub(crate) fn new(parsed: &'p Hoi4ParsedText<'a>) -> Self {
+        Hoi4TextEditor {
+            parsed,
+            edits: Vec::new(),
+        }
+    }
+
+    /// Replaces the scalar at the path with the given text. A quoted value
+    /// stays quoted, with quotes and backslashes in the replacement escaped.
+    /// An unquoted value stays unquoted, so the replacement must be a single
+    /// scalar: non-empty and without whitespace, quotes, comments, operators
+    /// or braces. Setting the same value twice keeps the latest replacement.
+    pub fn set(&mut self, path: &str, value: impl AsRef<[u8]>) -> Result<(), Hoi4Error> {
+        let segments: Vec<&str> = path.split('.').collect();
+        let (scalar, quoted) =
+            find_in_object(self.parsed.reader(), &segments).ok_or_else(|| {
+                Hoi4ErrorKind::PathNotFound {
+                    path: path.to_string(),
+                }
+            })?;
+
+        let value = value.as_ref();
+        let value = if quoted {
+            escape_quoted(value)
+        } else if is_unquoted_scalar(value) {
+            value.to_vec()
+        } else {
+            return Err(Hoi4ErrorKind::InvalidEditValue {
+                path: path.to_string(),
+                value: String::from_utf8_lossy(value).into_owned(),
+            }
+            .into());
+        };
+
+        let data = self.parsed.data();
+        let start = scalar.as_bytes().as_ptr() as usize - data.as_ptr() as usize;
+        let span = start..start + scalar.as_bytes().len();
+        match self.edits.iter_mut().find(|(range, _)| *range == span) {
+            Some((_, existing)) => *existing = value,
+            None => self.edits.push((span, value)),
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if no edits have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Writes the document with the edits applied
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Hoi4Error> {
+        let mut edits: Vec<_> = self.edits.iter().collect();
+        edits.sort_unstable_by_key(|(range, _)| range.start);
+
+        let data = self.parsed.data();
+        let mut pos = 0;
+        for (range, value) in edits {
+            writer.write_all(&data[pos..range.start])?;
+            writer.write_all(value)?;
+            pos = range.end;
+        }
+
+        writer.write_all(&data[pos..])?;
+        Ok(())
+    }
+}
+
+fn escape_quoted(value: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(value.len());
+    for &byte in value {
+        if matches!(byte, b'"' | b'\\') {
+            result.push(b'\\');
+        }
+        result.push(byte);
+    }
+    result
+}
+
+fn is_unquoted_scalar(value: &[u8]) -> bool {
+    !value.is_empty()
+        && !value.iter().any(|x| {
+            x.is_ascii_whitespace()
+                || matches!(
+                    x,
+                    b'"' | b'#' | b'=' | b'<' | b'>' | b'!' | b'?' | b'{' | b'}'
+                )
+        })
+}
+
+/// Finds the scalar at the path and whether it is quoted
+fn find_in_object<'a>(
+    reader: ObjectReader<'a, '_, Utf8Encoding>,
+    segments: &[&str],
+) -> Option<(Scalar<'a>, bool)> {
+    let (key, rest) = segments.split_first()?;
+    reader
+        .fields()
+        .filter(|(k, _, _)| k.read_str() == *key)
+        .find_map(|(_, _, value)| find_in_value(value, rest))
+}
+
+fn find_in_value<'a>(
+    value: ValueReader<'a, '_, Utf8Encoding>,
+    segments: &[&str],
+) -> Option<(Scalar<'a>, bool)> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return match value.token() {
+            TextToken::Unquoted(x) => Some((*x, false)),
+            TextToken::Quoted(x) => Some((*x, true)),
+            _ => None,
+        };
+    };
+
+    match value.token() {
+        // A header (eg: `rgb { 1 2 3 }`) is read as the header and its body
+        TextToken::Header(_) => {
+            let body = value.read_array().ok()?.values().nth(1)?;
+            find_in_value(body, segments)
+        }
+        TextToken::Array { .. } => {
+            let index: usize = segment.parse().ok()?;
+            let array = value.read_array().ok()?;
+            let value = array.values().nth(index)?;
+            find_in_value(value, rest)
+        }
+        TextToken::Object { .. } => find_in_object(value.read_object().ok()?, segments),
+        _ => None,
+    }
+}
@@ -3,18 +3,68 @@ use std::{fmt, io};
 use jomini::binary;
 
 /// A Hoi4 Error
-#[derive(thiserror::Error, Debug)]
-#[error(transparent)]
-pub struct Hoi4Error(#[from] Box<Hoi4ErrorKind>);
+#[derive(Debug)]
+pub struct Hoi4Error(Box<ErrorImpl>);
+
+#[derive(Debug)]
+struct ErrorImpl {
+    kind: Hoi4ErrorKind,
+    location: Option<ErrorLocation>,
+}
 
 impl Hoi4Error {
     pub(crate) fn new(kind: Hoi4ErrorKind) -> Hoi4Error {
-        Hoi4Error(Box::new(kind))
+        Hoi4Error(Box::new(ErrorImpl {
+            kind,
+            location: None,
+        }))
     }
 
     /// Return the specific type of error
     pub fn kind(&self) -> &Hoi4ErrorKind {
-        &self.0
+        &self.0.kind
+    }
+
+    /// Where in the input the error occurred, if known
+    pub fn location(&self) -> Option<&ErrorLocation> {
+        self.0.location.as_ref()
+    }
+
+    /// The broad category of the error. See [`Hoi4ErrorKind::category`].
+    pub fn category(&self) -> ErrorCategory {
+        self.0.kind.category()
+    }
+
+    /// See [`Hoi4ErrorKind::is_recoverable`]
+    pub fn is_recoverable(&self) -> bool {
+        self.0.kind.is_recoverable()
+    }
+
+    /// Attaches a location to the error unless it already has one
+    pub(crate) fn with_location(mut self, location: ErrorLocation) -> Hoi4Error {
+        self.0.location.get_or_insert(location);
+        self
+    }
+}
+
+impl fmt::Display for Hoi4Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0.location {
+            Some(location) => write!(f, "{} ({})", self.0.kind, location),
+            None => self.0.kind.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Hoi4Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        std::error::Error::source(&self.0.kind)
+    }
+}
+
+impl From<Box<Hoi4ErrorKind>> for Hoi4Error {
+    fn from(err: Box<Hoi4ErrorKind>) -> Self {
+        Hoi4Error::new(*err)
     }
 }
 
@@ -24,8 +74,34 @@ impl From<Hoi4ErrorKind> for Hoi4Error {
     }
 }
 
+/// Where in the input an error occurred
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorLocation {
+    /// Byte offset into the save body of the token being processed, if
+    /// known
+    pub offset: Option<usize>,
+
+    /// Dot separated keys of the field being processed (eg:
+    /// `countries.GER.politics`), if known. The root object is the empty
+    /// path. Melting a reader only reports the offset, as the keys are not
+    /// tracked unless a melt option needs them.
+    pub path: Option<String>,
+}
+
+impl fmt::Display for ErrorLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.path, self.offset) {
+            (Some(path), Some(offset)) => write!(f, "path: `{}`, offset: {}", path, offset),
+            (Some(path), None) => write!(f, "path: `{}`", path),
+            (None, Some(offset)) => write!(f, "offset: {}", offset),
+            (None, None) => write!(f, "unknown location"),
+        }
+    }
+}
+
 /// Specific type of error
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum Hoi4ErrorKind {
     #[error("unable to parse due to: {0}")]
     Parse(#[from] jomini::Error),
@@ -51,11 +127,166 @@ pub enum Hoi4ErrorKind {
     #[error("country tags must contain only ascii letters")]
     CountryTagInvalidCharacters,
 
+    #[error("no value found at path: {path}")]
+    PathNotFound { path: String },
+
+    #[error("replacement for `{path}` is not a single scalar: {value}")]
+    InvalidEditValue { path: String, value: String },
+
+    #[error("unable to freeze to binary due to: {msg}")]
+    Freeze { msg: String },
+
+    #[error("unable to serialize to text due to: {msg}")]
+    Serialize { msg: String },
+
+    #[error("expected a hex token id and name on line {line} of the token table")]
+    InvalidTokenLine { line: usize },
+
+    #[error("invalid token table: {msg}")]
+    InvalidTokenTable { msg: String },
+
     #[error("unexpected end of file")]
     Eof,
 
+    #[error("unbalanced open and close tokens at offset {offset}")]
+    UnbalancedContainers { offset: usize },
+
+    #[error("invalid utf-8 at offset {offset}")]
+    InvalidUtf8 { offset: usize },
+
+    #[error("invalid value: {msg}")]
+    InvalidValue { msg: String },
+
     #[error("io error: {0}")]
     Io(#[from] io::Error),
+
+    /// The variants for compressed saves exist without the `compression`
+    /// feature, so matching on them doesn't depend on the enabled features
+    #[error("unable to read zip archive: {0}")]
+    Zip(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("zip archive does not contain a save")]
+    ZipMissingSave,
+
+    #[error("unsupported zip compression method: {method}")]
+    ZipUnsupportedCompression { method: u16 },
+
+    #[error("decompressed save exceeds the limit of {limit} bytes")]
+    DecompressedSizeLimit { limit: u64 },
+}
+
+impl Hoi4ErrorKind {
+    /// The broad category of the error, eg: for giving feedback on why a
+    /// save could not be read
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Hoi4ErrorKind::Parse(e) | Hoi4ErrorKind::Deserialize(e) => match e.kind() {
+                jomini::ErrorKind::Eof => ErrorCategory::Truncated,
+                jomini::ErrorKind::StackEmpty { .. }
+                | jomini::ErrorKind::InvalidEmptyObject { .. }
+                | jomini::ErrorKind::InvalidSyntax { .. }
+                | jomini::ErrorKind::BufferFull => ErrorCategory::InvalidStructure,
+                jomini::ErrorKind::Deserialize(e) => match e.kind() {
+                    jomini::DeserializeErrorKind::UnknownToken { .. } => {
+                        ErrorCategory::UnknownToken
+                    }
+                    jomini::DeserializeErrorKind::Scalar(_) => ErrorCategory::InvalidValue,
+                    _ => ErrorCategory::Deserialize,
+                },
+                jomini::ErrorKind::Io(e) => io_category(e),
+            },
+            Hoi4ErrorKind::UnknownToken { .. } => ErrorCategory::UnknownToken,
+            Hoi4ErrorKind::DeserializeImpl { .. } => ErrorCategory::Deserialize,
+            Hoi4ErrorKind::InvalidDate(_)
+            | Hoi4ErrorKind::InvalidValue { .. }
+            | Hoi4ErrorKind::InvalidEditValue { .. }
+            | Hoi4ErrorKind::CountryTagIncorrectSize
+            | Hoi4ErrorKind::CountryTagInvalidCharacters => ErrorCategory::InvalidValue,
+            Hoi4ErrorKind::UnknownHeader => ErrorCategory::UnsupportedFormat,
+            Hoi4ErrorKind::Eof => ErrorCategory::Truncated,
+            Hoi4ErrorKind::UnbalancedContainers { .. } => ErrorCategory::InvalidStructure,
+            Hoi4ErrorKind::InvalidUtf8 { .. } => ErrorCategory::InvalidUtf8,
+            Hoi4ErrorKind::Io(e) => io_category(e),
+            Hoi4ErrorKind::PathNotFound { .. }
+            | Hoi4ErrorKind::Freeze { .. }
+            | Hoi4ErrorKind::Serialize { .. }
+            | Hoi4ErrorKind::InvalidTokenLine { .. }
+            | Hoi4ErrorKind::InvalidTokenTable { .. } => ErrorCategory::Other,
+            Hoi4ErrorKind::Zip(_)
+            | Hoi4ErrorKind::ZipMissingSave
+            | Hoi4ErrorKind::ZipUnsupportedCompression { .. }
+            | Hoi4ErrorKind::DecompressedSizeLimit { .. } => ErrorCategory::Archive,
+        }
+    }
+
+    /// Returns true if the error affects only part of the save, so the rest
+    /// may still be read: a truncated save up to where it ends, and unknown
+    /// tokens or invalid dates by not failing on them (see
+    /// [`FailedResolveStrategy`](crate::FailedResolveStrategy)).
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Hoi4ErrorKind::InvalidDate(_) => true,
+            _ => matches!(
+                self.category(),
+                ErrorCategory::Truncated | ErrorCategory::UnknownToken
+            ),
+        }
+    }
+
+    /// The byte offset reported by the error itself, if any
+    pub(crate) fn offset(&self) -> Option<usize> {
+        match self {
+            Hoi4ErrorKind::Parse(e) | Hoi4ErrorKind::Deserialize(e) => e.offset(),
+            Hoi4ErrorKind::UnbalancedContainers { offset }
+            | Hoi4ErrorKind::InvalidUtf8 { offset } => Some(*offset),
+            _ => None,
+        }
+    }
+}
+
+fn io_category(err: &io::Error) -> ErrorCategory {
+    if err.kind() == io::ErrorKind::UnexpectedEof {
+        ErrorCategory::Truncated
+    } else {
+        ErrorCategory::Io
+    }
+}
+
+/// Broad classification of a [`Hoi4ErrorKind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// The save ended unexpectedly (eg: a partially written autosave)
+    Truncated,
+
+    /// The open and close tokens in the save are unbalanced or the save
+    /// is otherwise malformed
+    InvalidStructure,
+
+    /// Text that must be UTF-8, like the names in a token table, is not.
+    /// Strings within a save are decoded lossily and never produce this.
+    InvalidUtf8,
+
+    /// A value in the save could not be decoded (eg: an invalid date)
+    InvalidValue,
+
+    /// A binary token could not be resolved
+    UnknownToken,
+
+    /// The data is not a recognized save
+    UnsupportedFormat,
+
+    /// The compressed archive containing the save could not be read
+    Archive,
+
+    /// The save did not match the type being deserialized
+    Deserialize,
+
+    /// An IO error occurred
+    Io,
+
+    /// An error unrelated to the contents of a save (eg: an invalid token
+    /// table)
+    Other,
 }
 
 impl serde::de::Error for Hoi4Error {
@@ -74,6 +305,44 @@ impl From<io::Error> for Hoi4Error {
 
 impl From<jomini::Error> for Hoi4Error {
     fn from(value: jomini::Error) -> Self {
+        Self::from(classify(value))
+    }
+}
+
+/// Converts the jomini errors that have a more specific kind
+fn classify(err: jomini::Error) -> Hoi4ErrorKind {
+    match err.kind() {
+        jomini::ErrorKind::Eof => Hoi4ErrorKind::Eof,
+        jomini::ErrorKind::StackEmpty { offset }
+        | jomini::ErrorKind::InvalidEmptyObject { offset } => {
+            Hoi4ErrorKind::UnbalancedContainers { offset: *offset }
+        }
+        jomini::ErrorKind::Deserialize(e) => match e.kind() {
+            jomini::DeserializeErrorKind::UnknownToken { token_id } => {
+                match u16::try_from(*token_id) {
+                    Ok(token_id) => Hoi4ErrorKind::UnknownToken { token_id },
+                    Err(_) => Hoi4ErrorKind::Parse(err),
+                }
+            }
+            jomini::DeserializeErrorKind::Scalar(e) => {
+                Hoi4ErrorKind::InvalidValue { msg: e.to_string() }
+            }
+            _ => Hoi4ErrorKind::Parse(err),
+        },
+        _ => Hoi4ErrorKind::Parse(err),
+    }
+}
+
+#[cfg(feature = "compression")]
+impl From<rawzip::Error> for Hoi4ErrorKind {
+    fn from(value: rawzip::Error) -> Self {
+        Hoi4ErrorKind::Zip(Box::new(value))
+    }
+}
+
+#[cfg(feature = "compression")]
+impl From<rawzip::Error> for Hoi4Error {
+    fn from(value: rawzip::Error) -> Self {
         Self::from(Hoi4ErrorKind::from(value))
     }
 }
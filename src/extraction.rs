@@ -7,3 +7,21 @@ pub enum Encoding {
     /// Plaintext save
     Plaintext,
 }
+
+/// Describes the container that a save was wrapped in. Saves written by
+/// the game are not wrapped, but saves that are shared or archived often
+/// are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    /// The save was not wrapped
+    Raw,
+
+    /// The save was the first file in a zip archive
+    Zip,
+
+    /// The save was gzip compressed
+    Gzip,
+
+    /// The save was zlib compressed
+    Zlib,
+}
@@ -4,22 +4,35 @@ use std::{
     io::{self, Read, Write},
 };
 
+pub use crate::edit::Hoi4TextEditor;
+
 use crate::{
-    flavor::Hoi4Flavor, melt, models::Hoi4Save, Encoding, Hoi4Error, Hoi4ErrorKind, MeltOptions,
-    MeltedDocument,
+    binary::{self, BinaryToken},
+    flavor::Hoi4Flavor,
+    freeze::{self, ReverseTokenResolver},
+    json::JsonOptions,
+    json_stream, melt,
+    models::Hoi4Save,
+    path::{KeyTrail, Tracked},
+    report::{DeserializeReport, ReportingResolver},
+    summary::{self, SaveSummary},
+    Container, Encoding, ErrorLocation, Hoi4Error, Hoi4ErrorKind, MeltConfig, MeltOptions,
+    MeltedDocument, Truncation,
 };
+#[cfg(feature = "compression")]
+use crate::{compression, Decompressor};
 use jomini::{binary::TokenResolver, text::ObjectReader, TextDeserializer, TextTape, Utf8Encoding};
 use serde::de::DeserializeOwned;
 
-const TXT_HEADER: &[u8] = b"HOI4txt";
+pub(crate) const TXT_HEADER: &[u8] = b"HOI4txt";
 const BIN_HEADER: &[u8] = b"HOI4bin";
 
-enum FileHeader {
+pub(crate) enum FileHeader {
     Text,
     Binary,
 }
 
-fn file_header(data: &[u8]) -> Option<(FileHeader, &[u8])> {
+pub(crate) fn file_header(data: &[u8]) -> Option<(FileHeader, &[u8])> {
     if data.len() < TXT_HEADER.len() {
         return None;
     }
@@ -43,28 +56,137 @@ impl Hoi4File {
         match file_header(data) {
             Some((FileHeader::Text, data)) => Ok(Hoi4SliceFile {
                 kind: Hoi4SliceFileKind::Text(Hoi4Text(data)),
+                container: Container::Raw,
             }),
             Some((FileHeader::Binary, data)) => Ok(Hoi4SliceFile {
                 kind: Hoi4SliceFileKind::Binary(Hoi4Binary(data)),
+                container: Container::Raw,
             }),
             None => Err(Hoi4Error::new(Hoi4ErrorKind::UnknownHeader)),
         }
     }
 
     /// Parse a HOI4 file from a file
-    pub fn from_file(mut file: File) -> Result<Hoi4FsFile, Hoi4Error> {
+    pub fn from_file(file: File) -> Result<Hoi4FsFile, Hoi4Error> {
+        Self::from_reader(file)
+    }
+
+    /// Memory maps the file so that it can be parsed as a slice without
+    /// first being copied into memory. Recommended for large saves.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the map is alive.
+    /// See [`memmap2::Mmap`] for details.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn from_mmap(file: &File) -> Result<Hoi4MmapFile, Hoi4Error> {
+        let mmap = unsafe { memmap2::Mmap::map(file)? };
+        if file_header(&mmap).is_none() {
+            return Err(Hoi4Error::new(Hoi4ErrorKind::UnknownHeader));
+        }
+
+        Ok(Hoi4MmapFile { mmap })
+    }
+
+    /// Parse a HOI4 file from a reader. Only the header is consumed, so
+    /// the reader can be a stream (eg: a request body).
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Hoi4ReaderFile<R>, Hoi4Error> {
         let mut header = [0u8; TXT_HEADER.len()];
-        file.read_exact(&mut header)?;
+        reader.read_exact(&mut header)?;
         match file_header(&header) {
-            Some((FileHeader::Text, _)) => Ok(Hoi4FsFile {
-                kind: Hoi4FsFileKind::Text(Hoi4TextReader::from_reader(file)),
+            Some((FileHeader::Text, _)) => Ok(Hoi4ReaderFile {
+                kind: Hoi4ReaderFileKind::Text(Hoi4TextReader::from_reader(reader)),
+                container: Container::Raw,
             }),
-            Some((FileHeader::Binary, _)) => Ok(Hoi4FsFile {
-                kind: Hoi4FsFileKind::Binary(Hoi4Binary(file)),
+            Some((FileHeader::Binary, _)) => Ok(Hoi4ReaderFile {
+                kind: Hoi4ReaderFileKind::Binary(Hoi4Binary(reader)),
+                container: Container::Raw,
             }),
             None => Err(Hoi4Error::new(Hoi4ErrorKind::UnknownHeader)),
         }
     }
+
+    /// Parse a HOI4 file from an async reader. Only the header is consumed.
+    /// See [`async_file`](crate::async_file) for when the rest is buffered.
+    ///
+    /// Melting a binary save moves the token resolver to tokio's blocking
+    /// thread pool, so [`Hoi4AsyncFile::melt`](crate::async_file::Hoi4AsyncFile::melt)
+    /// needs a resolver that is `Send + 'static`, like an owned table or an
+    /// `Arc`, rather than a borrow.
+    #[cfg(feature = "tokio")]
+    pub async fn from_async_reader<R>(
+        reader: R,
+    ) -> Result<crate::async_file::Hoi4AsyncFile<R>, Hoi4Error>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        crate::async_file::Hoi4AsyncFile::from_reader(reader).await
+    }
+
+    /// Parse a HOI4 file from a slice of data that may be zipped, gzipped,
+    /// or zlib compressed. A wrapped save is decompressed into `buf`.
+    #[cfg(feature = "compression")]
+    pub fn from_compressed_slice<'a>(
+        data: &'a [u8],
+        buf: &'a mut Vec<u8>,
+    ) -> Result<Hoi4SliceFile<'a>, Hoi4Error> {
+        let container = compression::detect(data);
+        if container == Container::Raw {
+            return Self::from_slice(data);
+        }
+
+        buf.clear();
+        compression::decompress_slice(data, container, buf)?;
+        let mut file = Self::from_slice(buf)?;
+        file.container = container;
+        Ok(file)
+    }
+
+    /// Parse a HOI4 file from a reader that may be zipped, gzipped, or zlib
+    /// compressed. See [`Decompressor`] for how each container is read.
+    #[cfg(feature = "compression")]
+    pub fn from_compressed_reader<R: Read>(
+        reader: R,
+    ) -> Result<Hoi4ReaderFile<Decompressor<R>>, Hoi4Error> {
+        let reader = Decompressor::new(reader)?;
+        let container = reader.container();
+        let mut file = Self::from_reader(reader)?;
+        file.container = container;
+        Ok(file)
+    }
+}
+
+/// A memory mapped HOI4 save with a validated header
+#[cfg(feature = "mmap")]
+#[derive(Debug)]
+pub struct Hoi4MmapFile {
+    mmap: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl Hoi4MmapFile {
+    /// The mapped data, including the header
+    pub fn as_slice(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// A view over the mapped data that can be parsed and melted
+    pub fn file(&self) -> Hoi4SliceFile<'_> {
+        Hoi4File::from_slice(&self.mmap).expect("header to be validated on map")
+    }
+
+    /// Parses the mapped data as a text save
+    pub fn parse_text(&self) -> Result<Hoi4ParsedText<'_>, Hoi4Error> {
+        Hoi4ParsedText::from_slice(&self.mmap)
+    }
+
+    /// Parses the mapped data as a binary save
+    pub fn parse_binary<R>(&self, resolver: R) -> Result<Hoi4ParsedBinary<'_, R>, Hoi4Error>
+    where
+        R: TokenResolver,
+    {
+        Hoi4ParsedBinary::from_slice(&self.mmap, resolver)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -76,6 +198,7 @@ pub enum Hoi4SliceFileKind<'a> {
 #[derive(Debug, Clone)]
 pub struct Hoi4SliceFile<'a> {
     pub kind: Hoi4SliceFileKind<'a>,
+    container: Container,
 }
 
 impl<'a> Hoi4SliceFile<'a> {
@@ -94,6 +217,11 @@ impl<'a> Hoi4SliceFile<'a> {
         }
     }
 
+    /// The container the save was unwrapped from
+    pub fn container(&self) -> Container {
+        self.container
+    }
+
     pub fn parse_save<R>(&self, resolver: R) -> Result<Hoi4Save, Hoi4Error>
     where
         R: TokenResolver,
@@ -108,13 +236,101 @@ impl<'a> Hoi4SliceFile<'a> {
     {
         match &self.kind {
             Hoi4SliceFileKind::Text(data) => data.deserializer().deserialize(),
-            Hoi4SliceFileKind::Binary(data) => data.clone().deserializer(resolver).deserialize(),
+            Hoi4SliceFileKind::Binary(data) => {
+                Hoi4Modeller::from_slice(data.0, resolver, Encoding::Binary).deserialize()
+            }
+        }
+    }
+
+    /// Deserializes a [`Hoi4Save`] and reports the binary tokens that the
+    /// resolver could not name. The report is filled as the save is
+    /// deserialized: each unknown token is listed with the paths where the
+    /// model needed it, and a field keyed by an unknown token is skipped
+    /// without looking inside it. Text saves have no tokens, so their report
+    /// is always empty. See [`DeserializeReport`].
+    pub fn parse_save_with_report<R>(
+        &self,
+        resolver: R,
+    ) -> Result<(Hoi4Save, DeserializeReport), Hoi4Error>
+    where
+        R: TokenResolver,
+    {
+        self.parse_with_report(resolver)
+    }
+
+    /// Deserializes the save like [`Self::parse`] and reports the tokens
+    /// that could not be resolved and where they were encountered. Text
+    /// saves always have an empty report.
+    pub fn parse_with_report<T, R>(&self, resolver: R) -> Result<(T, DeserializeReport), Hoi4Error>
+    where
+        R: TokenResolver,
+        T: DeserializeOwned,
+    {
+        match &self.kind {
+            Hoi4SliceFileKind::Text(data) => {
+                Ok((data.deserializer().deserialize()?, DeserializeReport::new()))
+            }
+            Hoi4SliceFileKind::Binary(data) => {
+                Hoi4Modeller::from_slice(data.0, resolver, Encoding::Binary)
+                    .deserialize_with_report()
+            }
+        }
+    }
+
+    /// Deserializes a [`Hoi4Save`] from a binary save that may have been cut
+    /// short, like a partially written autosave, and returns where the input
+    /// ended if it was. The save is melted to text before it is deserialized:
+    /// the field that was being written when the input ended is dropped, the
+    /// containers left open are closed, and the rest of the save is kept. A
+    /// [`Hoi4Save`] needs its `player` and `date` fields, so an error is still
+    /// returned if the cut came before them. See [`Self::parse_recovered`].
+    pub fn parse_save_recovered<R>(
+        &self,
+        resolver: R,
+    ) -> Result<(Hoi4Save, Option<Truncation>), Hoi4Error>
+    where
+        R: TokenResolver,
+    {
+        self.parse_recovered(resolver)
+    }
+
+    /// Deserializes as much of a binary save that was cut short as possible,
+    /// returning where the input ended if it was. The save is melted with
+    /// [`MeltOptions::recover`] and the melted text is deserialized, so any
+    /// fields the model requires must have been written before the cut.
+    /// Text saves are deserialized as normal.
+    pub fn parse_recovered<T, R>(&self, resolver: R) -> Result<(T, Option<Truncation>), Hoi4Error>
+    where
+        R: TokenResolver,
+        T: DeserializeOwned,
+    {
+        match &self.kind {
+            Hoi4SliceFileKind::Text(data) => Ok((data.deserializer().deserialize()?, None)),
+            Hoi4SliceFileKind::Binary(data) => {
+                let mut melted = Vec::new();
+                let options = MeltOptions::new().verbatim(true).recover(true);
+                let doc = melt::melt(data.0, &mut melted, resolver, options.into())?;
+                let value = Hoi4Text(&melted).deserializer().deserialize()?;
+                Ok((value, doc.truncation().cloned()))
+            }
+        }
+    }
+
+    /// Reads the metadata at the start of the save without deserializing
+    /// the rest of it
+    pub fn summary<R>(&self, resolver: R) -> Result<SaveSummary, Hoi4Error>
+    where
+        R: TokenResolver,
+    {
+        match &self.kind {
+            Hoi4SliceFileKind::Text(data) => summary::text_summary(data.0),
+            Hoi4SliceFileKind::Binary(data) => summary::binary_summary(data.0, resolver),
         }
     }
 
     pub fn melt<Resolver, Writer>(
         &self,
-        options: MeltOptions,
+        options: impl Into<MeltConfig>,
         resolver: Resolver,
         mut output: Writer,
     ) -> Result<MeltedDocument, Hoi4Error>
@@ -125,10 +341,6 @@ impl<'a> Hoi4SliceFile<'a> {
         match &self.kind {
             Hoi4SliceFileKind::Text(data) => {
                 output.write_all(TXT_HEADER)?;
-                output.write_all(
-                    b"
-",
-                )?;
                 output.write_all(data.0)?;
                 Ok(MeltedDocument::new())
             }
@@ -138,7 +350,7 @@ impl<'a> Hoi4SliceFile<'a> {
                     b"
 ",
                 )?;
-                let doc = melt::melt(data.0, &mut output, resolver, options)?;
+                let doc = melt::melt_slice(data.0, &mut output, resolver, options.into())?;
                 output.write_all(
                     b"
 ",
@@ -149,44 +361,110 @@ impl<'a> Hoi4SliceFile<'a> {
     }
 }
 
-pub enum Hoi4FsFileKind {
-    Text(Hoi4TextReader<File>),
-    Binary(Hoi4Binary<File>),
+pub enum Hoi4ReaderFileKind<R> {
+    Text(Hoi4TextReader<R>),
+    Binary(Hoi4Binary<R>),
 }
 
-pub struct Hoi4FsFile {
-    pub kind: Hoi4FsFileKind,
+pub struct Hoi4ReaderFile<R> {
+    pub kind: Hoi4ReaderFileKind<R>,
+    container: Container,
 }
 
-impl Hoi4FsFile {
-    pub fn kind(&self) -> &Hoi4FsFileKind {
+pub type Hoi4FsFileKind = Hoi4ReaderFileKind<File>;
+pub type Hoi4FsFile = Hoi4ReaderFile<File>;
+
+impl<R: Read> Hoi4ReaderFile<R> {
+    pub fn kind(&self) -> &Hoi4ReaderFileKind<R> {
         &self.kind
     }
 
-    pub fn kind_mut(&mut self) -> &mut Hoi4FsFileKind {
+    pub fn kind_mut(&mut self) -> &mut Hoi4ReaderFileKind<R> {
         &mut self.kind
     }
 
     pub fn encoding(&self) -> Encoding {
         match &self.kind {
-            Hoi4FsFileKind::Text(_) => Encoding::Plaintext,
-            Hoi4FsFileKind::Binary(_) => Encoding::Binary,
+            Hoi4ReaderFileKind::Text(_) => Encoding::Plaintext,
+            Hoi4ReaderFileKind::Binary(_) => Encoding::Binary,
         }
     }
 
+    /// The container the save was unwrapped from
+    pub fn container(&self) -> Container {
+        self.container
+    }
+
     pub fn parse_save<RES>(&mut self, resolver: RES) -> Result<Hoi4Save, Hoi4Error>
     where
         RES: TokenResolver,
+    {
+        self.parse(resolver)
+    }
+
+    pub fn parse<T, RES>(&mut self, resolver: RES) -> Result<T, Hoi4Error>
+    where
+        RES: TokenResolver,
+        T: DeserializeOwned,
     {
         match &mut self.kind {
-            Hoi4FsFileKind::Text(file) => file.as_ref().deserializer().deserialize(),
-            Hoi4FsFileKind::Binary(file) => file.as_ref().deserializer(resolver).deserialize(),
+            Hoi4ReaderFileKind::Text(file) => file.as_mut().deserializer().deserialize(),
+            Hoi4ReaderFileKind::Binary(file) => file.as_mut().deserializer(resolver).deserialize(),
+        }
+    }
+
+    /// Deserializes a [`Hoi4Save`] and reports the binary tokens that the
+    /// resolver could not name. See
+    /// [`Hoi4SliceFile::parse_save_with_report`] for when the report is
+    /// filled and what it contains.
+    pub fn parse_save_with_report<RES>(
+        &mut self,
+        resolver: RES,
+    ) -> Result<(Hoi4Save, DeserializeReport), Hoi4Error>
+    where
+        RES: TokenResolver,
+    {
+        self.parse_with_report(resolver)
+    }
+
+    /// Deserializes the save like [`Self::parse`] and reports the tokens
+    /// that could not be resolved and where they were encountered. Text
+    /// saves always have an empty report.
+    pub fn parse_with_report<T, RES>(
+        &mut self,
+        resolver: RES,
+    ) -> Result<(T, DeserializeReport), Hoi4Error>
+    where
+        RES: TokenResolver,
+        T: DeserializeOwned,
+    {
+        match &mut self.kind {
+            Hoi4ReaderFileKind::Text(file) => {
+                let value = file.as_mut().deserializer().deserialize()?;
+                Ok((value, DeserializeReport::new()))
+            }
+            Hoi4ReaderFileKind::Binary(file) => file
+                .as_mut()
+                .deserializer(resolver)
+                .deserialize_with_report(),
+        }
+    }
+
+    /// Reads the metadata at the start of the save without deserializing
+    /// the rest of it. The reader is consumed as it is left partially read.
+    pub fn summary<RES>(self, resolver: RES) -> Result<SaveSummary, Hoi4Error>
+    where
+        RES: TokenResolver,
+    {
+        match self.kind {
+            Hoi4ReaderFileKind::Text(file) => summary::text_summary(file.0),
+            Hoi4ReaderFileKind::Binary(file) => summary::binary_summary(file.0, resolver),
         }
     }
 
     pub fn melt<Resolver, Writer>(
         &mut self,
-        options: MeltOptions,
+        options: impl Into<MeltConfig>,
         resolver: Resolver,
         mut output: Writer,
     ) -> Result<MeltedDocument, Hoi4Error>
@@ -194,13 +472,18 @@ impl Hoi4FsFile {
         Resolver: TokenResolver,
         Writer: Write,
     {
-        output.write_all(b"HOI4txt")?;
+        output.write_all(TXT_HEADER)?;
         match &mut self.kind {
-            Hoi4FsFileKind::Text(file) => {
+            Hoi4ReaderFileKind::Text(file) => {
                 std::io::copy(&mut file.0, &mut output)?;
                 Ok(MeltedDocument::new())
             }
-            Hoi4FsFileKind::Binary(file) => file.melt(options, resolver, output),
+            Hoi4ReaderFileKind::Binary(file) => {
+                output.write_all(b"\n")?;
+                let doc = file.melt(options, resolver, &mut output)?;
+                output.write_all(b"\n")?;
+                Ok(doc)
+            }
         }
     }
 }
@@ -215,7 +498,7 @@ impl<'a> Hoi4Text<'a> {
     }
 
     pub fn deserializer(&self) -> Hoi4Modeller<'a, HashMap<u16, String>> {
-        Hoi4Modeller::from_reader(Box::new(self.0), HashMap::new(), Encoding::Plaintext)
+        Hoi4Modeller::from_slice(self.0, HashMap::new(), Encoding::Plaintext)
     }
 }
 
@@ -234,6 +517,10 @@ where
         Hoi4TextReader(&self.0)
     }
 
+    pub fn as_mut(&mut self) -> Hoi4TextReader<&mut R> {
+        Hoi4TextReader(&mut self.0)
+    }
+
     pub fn deserializer<'a>(self) -> Hoi4Modeller<'a, HashMap<u16, String>>
     where
         R: Read + 'a,
@@ -241,9 +528,9 @@ where
         Hoi4Modeller::from_reader(self.0, HashMap::new(), Encoding::Plaintext)
     }
 
-    // pub fn parse<T: DeserializeOwned>(&self) -> Result<T, Hoi4Error> {
-    //     self.deserializer().deserialize()
-    // }
+    pub fn parse<T: DeserializeOwned>(self) -> Result<T, Hoi4Error> {
+        self.deserializer().deserialize()
+    }
 }
 
 impl<R: Read> Read for Hoi4TextReader<R> {
@@ -267,6 +554,10 @@ where
         Hoi4Binary(&self.0)
     }
 
+    pub fn as_mut(&mut self) -> Hoi4Binary<&mut R> {
+        Hoi4Binary(&mut self.0)
+    }
+
     pub fn deserializer<'a, Resolver>(self, resolver: Resolver) -> Hoi4Modeller<'a, Resolver>
     where
         R: Read + 'a,
@@ -275,9 +566,17 @@ where
         Hoi4Modeller::from_reader(self.0, resolver, Encoding::Binary)
     }
 
+    pub fn parse<T, Resolver>(self, resolver: Resolver) -> Result<T, Hoi4Error>
+    where
+        T: DeserializeOwned,
+        Resolver: TokenResolver,
+    {
+        self.deserializer(resolver).deserialize()
+    }
+
     pub fn melt<Resolver, Writer>(
         &mut self,
-        options: MeltOptions,
+        options: impl Into<MeltConfig>,
         resolver: Resolver,
         mut output: Writer,
     ) -> Result<MeltedDocument, Hoi4Error>
@@ -285,38 +584,140 @@ where
         Resolver: TokenResolver,
         Writer: Write,
     {
-        melt::melt(&mut self.0, &mut output, resolver, options)
+        melt::melt(&mut self.0, &mut output, resolver, options.into())
+    }
+
+    /// Writes the save as JSON while reading it, without buffering the
+    /// tokens in memory. The output is the same as converting a
+    /// [`Hoi4ParsedBinary`] to JSON.
+    ///
+    /// Grouping duplicate keys with [`DuplicateKeyMode::Group`] needs every
+    /// field of an object before it can be written, so in that mode the
+    /// save is read into memory first.
+    ///
+    /// [`DuplicateKeyMode::Group`]: crate::json::DuplicateKeyMode::Group
+    pub fn json<Resolver, Writer>(
+        &mut self,
+        options: JsonOptions,
+        resolver: Resolver,
+        output: Writer,
+    ) -> Result<(), Hoi4Error>
+    where
+        Resolver: TokenResolver,
+        Writer: Write,
+    {
+        json_stream::write_json(&mut self.0, output, resolver, options)
+    }
+}
+
+impl<R: Read> Read for Hoi4Binary<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
     }
 }
 
 /// A parsed Hoi4 text document
 pub struct Hoi4ParsedText<'a> {
+    data: &'a [u8],
     tape: TextTape<'a>,
 }
 
 impl<'a> Hoi4ParsedText<'a> {
     pub fn from_slice(data: &'a [u8]) -> Result<Self, Hoi4Error> {
-        file_header(data)
+        let body = file_header(data)
             .filter(|(header, _)| matches!(header, FileHeader::Text))
-            .map(|(_, data)| data)
-            .ok_or_else(|| Hoi4ErrorKind::UnknownHeader.into())
-            .and_then(Self::from_raw)
+            .map(|(_, body)| body)
+            .ok_or(Hoi4ErrorKind::UnknownHeader)?;
+        Self::parse(data, body)
     }
 
     pub fn from_raw(data: &'a [u8]) -> Result<Self, Hoi4Error> {
-        let tape = TextTape::from_slice(data).map_err(Hoi4ErrorKind::Parse)?;
-        Ok(Hoi4ParsedText { tape })
+        Self::parse(data, data)
+    }
+
+    fn parse(data: &'a [u8], body: &'a [u8]) -> Result<Self, Hoi4Error> {
+        let tape = TextTape::from_slice(body)?;
+        Ok(Hoi4ParsedText { data, tape })
+    }
+
+    /// The data that was parsed, including the header if there was one
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Records edits to the save that can be written out without
+    /// disturbing the rest of the document
+    pub fn editor(&self) -> Hoi4TextEditor<'_, 'a> {
+        Hoi4TextEditor::new(self)
     }
 
     pub fn reader(&self) -> ObjectReader<'_, '_, Utf8Encoding> {
         self.tape.utf8_reader()
     }
+
+    /// Encodes the save as a binary save, the reverse of melting. Numbers
+    /// and dates are written with the same encodings that melting decodes,
+    /// so values survive a round trip as long as they fit the precision of
+    /// their encoding.
+    pub fn freeze<R, W>(&self, resolver: R, mut output: W) -> Result<(), Hoi4Error>
+    where
+        R: ReverseTokenResolver,
+        W: Write,
+    {
+        output.write_all(BIN_HEADER)?;
+        freeze::freeze(self.tape.tokens(), output, resolver)
+    }
+}
+
+/// A parsed Hoi4 binary document
+pub struct Hoi4ParsedBinary<'a, R> {
+    tokens: Vec<BinaryToken<'a>>,
+    resolver: R,
+}
+
+impl<'a, R> Hoi4ParsedBinary<'a, R>
+where
+    R: TokenResolver,
+{
+    pub fn from_slice(data: &'a [u8], resolver: R) -> Result<Self, Hoi4Error> {
+        file_header(data)
+            .filter(|(header, _)| matches!(header, FileHeader::Binary))
+            .map(|(_, data)| data)
+            .ok_or_else(|| Hoi4ErrorKind::UnknownHeader.into())
+            .and_then(|data| Self::from_raw(data, resolver))
+    }
+
+    pub fn from_raw(data: &'a [u8], resolver: R) -> Result<Self, Hoi4Error> {
+        let tokens = binary::parse_tokens(data, &resolver)?;
+        Ok(Hoi4ParsedBinary { tokens, resolver })
+    }
+
+    /// The parsed tokens, where open and close tokens reference each other
+    pub fn tokens(&self) -> &[BinaryToken<'a>] {
+        &self.tokens
+    }
+
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+
+    pub fn reader(&self) -> binary::ObjectReader<'_, 'a, R> {
+        binary::ObjectReader::root(&self.tokens, &self.resolver)
+    }
 }
 
 pub struct Hoi4Modeller<'obj, Resolver> {
     reader: Box<dyn Read + 'obj>,
-    resolver: Resolver,
+    resolver: ReportingResolver<Resolver>,
     encoding: Encoding,
+
+    /// The input when deserializing from a slice, used to find where an
+    /// error occurred
+    data: Option<&'obj [u8]>,
+
+    /// Whether the keys of the fields being deserialized are tracked, which
+    /// is only needed for a report or to find the path of an error
+    track: bool,
 }
 
 impl<'obj, Resolver: TokenResolver> Hoi4Modeller<'obj, Resolver> {
@@ -327,20 +728,77 @@ impl<'obj, Resolver: TokenResolver> Hoi4Modeller<'obj, Resolver> {
     ) -> Self {
         Hoi4Modeller {
             reader: Box::new(reader),
-            resolver,
+            resolver: ReportingResolver::new(resolver),
             encoding,
+            data: None,
+            track: false,
+        }
+    }
+
+    pub(crate) fn from_slice(data: &'obj [u8], resolver: Resolver, encoding: Encoding) -> Self {
+        Hoi4Modeller {
+            data: Some(data),
+            ..Self::from_reader(data, resolver, encoding)
         }
     }
 
+    /// Attaches the path of the field that failed to deserialize, when keys
+    /// are tracked, and its byte offset, when available
+    fn locate(&self, err: Hoi4Error) -> Hoi4Error {
+        let path = self.track.then(|| self.resolver.trail().path());
+        let offset = err
+            .kind()
+            .offset()
+            .or_else(|| match (self.data, self.encoding, &path) {
+                (Some(data), Encoding::Binary, Some(path)) => {
+                    melt::locate_field(data, self.resolver.inner(), path)
+                }
+                _ => None,
+            });
+        err.with_location(ErrorLocation { offset, path })
+    }
+
     pub fn encoding(&self) -> Encoding {
         self.encoding
     }
 
+    /// Deserializes the model. Keys are not tracked while deserializing, so
+    /// when deserializing from a slice fails, the slice is deserialized again
+    /// to find the path of the field that failed. Errors from a reader only
+    /// have an offset, when one is known.
     pub fn deserialize<T>(&mut self) -> Result<T, Hoi4Error>
     where
         T: DeserializeOwned,
     {
-        T::deserialize(self)
+        let err = match T::deserialize(&mut *self) {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+
+        let Some(data) = self.data.filter(|_| !self.track) else {
+            return Err(err);
+        };
+
+        self.reader = Box::new(data);
+        self.resolver.clear_trail();
+        self.track = true;
+        let located = T::deserialize(&mut *self).err();
+        self.track = false;
+        Err(located.unwrap_or(err))
+    }
+
+    /// Deserializes the model and reports the tokens that could not be
+    /// resolved along the way. Fields keyed by an unknown token are skipped.
+    pub fn deserialize_with_report<T>(&mut self) -> Result<(T, DeserializeReport), Hoi4Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.resolver.start_report();
+        self.track = true;
+        let result = T::deserialize(&mut *self);
+        self.track = false;
+        let report = self.resolver.take_report();
+        result.map(|value| (value, report))
     }
 }
 
@@ -367,18 +825,23 @@ impl<'de, 'a: 'de, Resolver: TokenResolver> serde::de::Deserializer<'de>
     where
         V: serde::de::Visitor<'de>,
     {
-        if matches!(self.encoding, Encoding::Binary) {
-            use jomini::binary::BinaryFlavor;
-            let flavor = Hoi4Flavor;
-            let mut deser = flavor
-                .deserializer()
-                .from_reader(&mut self.reader, &self.resolver);
-            Ok(deser.deserialize_struct(name, fields, visitor)?)
+        let reader = &mut self.reader;
+        let result = if self.track {
+            let trail = Some(self.resolver.trail());
+            deserialize_model(
+                reader,
+                self.encoding,
+                &self.resolver,
+                trail,
+                name,
+                fields,
+                visitor,
+            )
         } else {
-            let reader = jomini::text::TokenReader::new(&mut self.reader);
-            let mut deser = TextDeserializer::from_utf8_reader(reader);
-            Ok(deser.deserialize_struct(name, fields, visitor)?)
-        }
+            let resolver = self.resolver.inner();
+            deserialize_model(reader, self.encoding, resolver, None, name, fields, visitor)
+        };
+        result.map_err(|e| self.locate(e))
     }
 
     serde::forward_to_deserialize_any! {
@@ -387,3 +850,43 @@ impl<'de, 'a: 'de, Resolver: TokenResolver> serde::de::Deserializer<'de>
         tuple_struct map enum identifier ignored_any
     }
 }
+
+/// Deserializes a struct from the reader, recording the keys of its fields
+/// in the trail when one is given
+fn deserialize_model<'de, V, R, RES>(
+    reader: R,
+    encoding: Encoding,
+    resolver: &'de RES,
+    trail: Option<&KeyTrail>,
+    name: &'static str,
+    fields: &'static [&'static str],
+    visitor: V,
+) -> Result<V::Value, Hoi4Error>
+where
+    V: serde::de::Visitor<'de>,
+    R: Read,
+    RES: TokenResolver,
+{
+    use serde::Deserializer;
+    if matches!(encoding, Encoding::Binary) {
+        use jomini::binary::BinaryFlavor;
+        let mut deser = Hoi4Flavor.deserializer().from_reader(reader, resolver);
+        match trail {
+            Some(trail) => {
+                Tracked::new(&mut deser, trail).deserialize_struct(name, fields, visitor)
+            }
+            None => (&mut deser).deserialize_struct(name, fields, visitor),
+        }
+        .map_err(Hoi4Error::from)
+    } else {
+        let reader = jomini::text::TokenReader::new(reader);
+        let mut deser = TextDeserializer::from_utf8_reader(reader);
+        match trail {
+            Some(trail) => {
+                Tracked::new(&mut deser, trail).deserialize_struct(name, fields, visitor)
+            }
+            None => (&mut deser).deserialize_struct(name, fields, visitor),
+        }
+        .map_err(Hoi4Error::from)
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{
+    binary::{FIXED_SCALE, RGB_ID},
+    Hoi4Date, Hoi4Error, Hoi4ErrorKind,
+};
+use jomini::{text::Operator, Scalar, TextToken};
+use std::{collections::HashMap, hash::BuildHasher, io::Write};
+
+/// Resolves a field name to the binary token id that represents it. The
+/// reverse of a [`TokenResolver`](jomini::binary::TokenResolver).
+pub trait ReverseTokenResolver {
+    /// Returns the token id for the name, if known
+    fn resolve_name(&self, name: &str) -> Option<u16>;
+}
+
+impl<S: BuildHasher> ReverseTokenResolver for HashMap<String, u16, S> {
+    fn resolve_name(&self, name: &str) -> Option<u16> {
+        self.get(name).copied()
+    }
+}
+
+impl<S: BuildHasher> ReverseTokenResolver for HashMap<&str, u16, S> {
+    fn resolve_name(&self, name: &str) -> Option<u16> {
+        self.get(name).copied()
+    }
+}
+
+impl<T: ReverseTokenResolver + ?Sized> ReverseTokenResolver for &T {
+    fn resolve_name(&self, name: &str) -> Option<u16> {
+        (**self).resolve_name(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frame {
+    Object {
+        expecting_key: bool,
+    },
+    Array,
+
+    /// The channels that follow an `rgb` header, which are always u32s
+    Rgb,
+}
+
+/// Writes binary tokens, mirroring the encodings that melting decodes
+struct BinaryWriter<W, R> {
+    output: W,
+    resolver: R,
+    new_save_format: bool,
+}
+
+impl<W: Write, R: ReverseTokenResolver> BinaryWriter<W, R> {
+    fn write_id(&mut self, id: u16) -> Result<(), Hoi4Error> {
+        self.output.write_all(&id.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn write_string(&mut self, id: u16, data: &[u8]) -> Result<(), Hoi4Error> {
+        let len = u16::try_from(data.len()).map_err(|_| Hoi4ErrorKind::Freeze {
+            msg: format!("string of {} bytes is too long", data.len()),
+        })?;
+        self.write_id(id)?;
+        self.output.write_all(&len.to_le_bytes())?;
+        self.output.write_all(data)?;
+        Ok(())
+    }
+
+    fn write_i32(&mut self, x: i32) -> Result<(), Hoi4Error> {
+        self.write_id(0x000c)?;
+        self.output.write_all(&x.to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the token for a name that was written by the melter, either a
+    /// resolved name or an unknown token placeholder
+    fn write_token(&mut self, name: &str) -> Result<bool, Hoi4Error> {
+        let id = self.resolver.resolve_name(name).or_else(|| {
+            name.strip_prefix("__unknown_0x")
+                .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+        });
+
+        match id {
+            Some(id) => self.write_id(id).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    fn write_key(&mut self, scalar: Scalar, quoted: bool) -> Result<(), Hoi4Error> {
+        let data = scalar.as_bytes();
+        if !quoted {
+            let name = std::str::from_utf8(data).unwrap_or_default();
+            if self.write_token(name)? {
+                return Ok(());
+            }
+
+            if let Some(x) = scalar.to_i64().ok().and_then(|x| i32::try_from(x).ok()) {
+                return self.write_i32(x);
+            }
+
+            if let Ok(date) = Hoi4Date::parse(data) {
+                return self.write_i32(date.to_binary());
+            }
+        }
+
+        self.write_string(0x000f, data)
+    }
+
+    fn write_value(&mut self, scalar: Scalar, quoted: bool, key: &[u8]) -> Result<(), Hoi4Error> {
+        let data = scalar.as_bytes();
+        if quoted {
+            // Saves quote their dates (eg: `date="1936.1.1.12"`), which are
+            // still stored as integers
+            return match Hoi4Date::parse(data) {
+                Ok(date) => self.write_i32(date.to_binary()),
+                Err(_) => self.write_string(0x000f, data),
+            };
+        }
+
+        match data {
+            b"yes" => return self.write_bool(true),
+            b"no" => return self.write_bool(false),
+            _ => {}
+        }
+
+        if let Ok(x) = scalar.to_i64() {
+            if key == b"save_version" {
+                self.new_save_format = x >= 30;
+            }
+
+            if let Ok(x) = i32::try_from(x) {
+                return self.write_i32(x);
+            }
+
+            self.write_id(0x0317)?;
+            self.output.write_all(&x.to_le_bytes())?;
+            return Ok(());
+        }
+
+        if let Ok(x) = scalar.to_u64() {
+            self.write_id(0x029c)?;
+            self.output.write_all(&x.to_le_bytes())?;
+            return Ok(());
+        }
+
+        if let Ok(date) = Hoi4Date::parse(data) {
+            return self.write_i32(date.to_binary());
+        }
+
+        if let Ok(x) = scalar.to_f64() {
+            // Newer saves store decimals in an 8 byte 0x000d as
+            // hundred-thousandths. Older saves store thousandths in a 4 byte
+            // 0x000d, which is lossless for values with at most three
+            // decimal places. Otherwise the value is stored with 1/32768
+            // precision as an f64.
+            let decimals = data
+                .iter()
+                .position(|&c| c == b'.')
+                .map_or(0, |pos| data.len() - pos - 1);
+            let thousandths = (x * 1000.0).round();
+            if self.new_save_format {
+                self.write_id(0x000d)?;
+                let val = (x * FIXED_SCALE as f64).round() as i64;
+                self.output.write_all(&val.to_le_bytes())?;
+            } else if decimals <= 3
+                && thousandths >= f64::from(i32::MIN)
+                && thousandths <= f64::from(i32::MAX)
+            {
+                self.write_id(0x000d)?;
+                self.output.write_all(&(thousandths as i32).to_le_bytes())?;
+            } else {
+                self.write_id(0x0167)?;
+                let val = (x * 32768.0).round() as i64;
+                self.output.write_all(&val.to_le_bytes())?;
+            }
+            return Ok(());
+        }
+
+        let name = std::str::from_utf8(data).unwrap_or_default();
+        if self.write_token(name)? {
+            return Ok(());
+        }
+
+        self.write_string(0x0017, data)
+    }
+
+    fn write_bool(&mut self, x: bool) -> Result<(), Hoi4Error> {
+        self.write_id(0x000e)?;
+        self.output.write_all(&[u8::from(x)])?;
+        Ok(())
+    }
+}
+
+/// Encodes the tokens of a parsed text save as a binary save body
+pub(crate) fn freeze<W, R>(tokens: &[TextToken], output: W, resolver: R) -> Result<(), Hoi4Error>
+where
+    W: Write,
+    R: ReverseTokenResolver,
+{
+    let mut writer = BinaryWriter {
+        output,
+        resolver,
+        new_save_format: false,
+    };
+
+    let mut frames = vec![Frame::Object {
+        expecting_key: true,
+    }];
+    let mut key: &[u8] = &[];
+    let mut rgb = false;
+
+    for token in tokens {
+        let frame = frames.last_mut().ok_or_else(|| Hoi4ErrorKind::Freeze {
+            msg: String::from("unbalanced containers"),
+        })?;
+
+        match token {
+            TextToken::Unquoted(scalar) | TextToken::Quoted(scalar) => {
+                let quoted = matches!(token, TextToken::Quoted(_));
+                match frame {
+                    Frame::Object {
+                        expecting_key: expecting_key @ true,
+                    } => {
+                        writer.write_key(*scalar, quoted)?;
+                        writer.write_id(0x0001)?;
+                        key = scalar.as_bytes();
+                        *expecting_key = false;
+                    }
+                    Frame::Object { expecting_key } => {
+                        writer.write_value(*scalar, quoted, key)?;
+                        *expecting_key = true;
+                    }
+                    Frame::Array => writer.write_value(*scalar, quoted, &[])?,
+                    Frame::Rgb => {
+                        let channel = scalar.to_u64().ok().and_then(|x| u32::try_from(x).ok());
+                        let channel = channel.ok_or_else(|| Hoi4ErrorKind::Freeze {
+                            msg: format!("invalid rgb channel: {}", scalar),
+                        })?;
+                        writer.write_id(0x0014)?;
+                        writer.output.write_all(&channel.to_le_bytes())?;
+                    }
+                }
+            }
+            TextToken::Header(scalar) if scalar.as_bytes() == b"rgb" => {
+                writer.write_id(RGB_ID)?;
+                rgb = true;
+            }
+            TextToken::Header(scalar) => {
+                let name = std::str::from_utf8(scalar.as_bytes()).unwrap_or_default();
+                if !writer.write_token(name)? {
+                    writer.write_string(0x0017, scalar.as_bytes())?;
+                }
+            }
+            TextToken::Object { .. } | TextToken::Array { .. } => {
+                writer.write_id(0x0003)?;
+                if let Frame::Object { expecting_key } = frame {
+                    *expecting_key = true;
+                }
+
+                let child = match token {
+                    TextToken::Object { .. } => Frame::Object {
+                        expecting_key: true,
+                    },
+                    _ if rgb => Frame::Rgb,
+                    _ => Frame::Array,
+                };
+                frames.push(child);
+                rgb = false;
+            }
+            TextToken::End(_) => {
+                writer.write_id(0x0004)?;
+                frames.pop();
+            }
+            TextToken::MixedContainer => *frame = Frame::Array,
+            TextToken::Operator(Operator::Equal) => writer.write_id(0x0001)?,
+            TextToken::Operator(op) => {
+                return Err(Hoi4ErrorKind::Freeze {
+                    msg: format!("the {} operator has no binary encoding", op.symbol()),
+                }
+                .into())
+            }
+            TextToken::Parameter(_) | TextToken::UndefinedParameter(_) => {
+                return Err(Hoi4ErrorKind::Freeze {
+                    msg: String::from("parameters have no binary encoding"),
+                }
+                .into())
+            }
+        }
+    }
+
+    Ok(())
+}
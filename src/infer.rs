@@ -0,0 +1,364 @@
+use crate::{
+    binary::{self, BinaryToken, Lexer},
+    file::{file_header, Hoi4ParsedBinary, Hoi4ParsedText},
+    Hoi4Date, Hoi4Error,
+};
+use jomini::{binary::TokenResolver, text, TextToken, Utf8Encoding};
+use std::{collections::HashMap, io::Write};
+
+/// How many fields ahead to search for a matching key when the fields of a
+/// binary and text object fall out of step
+const RESYNC_WINDOW: usize = 16;
+
+/// A proposed name for a binary token id
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferredToken {
+    pub id: u16,
+    pub name: String,
+
+    /// The number of times the token was aligned with the name
+    pub votes: u32,
+
+    /// From 0 to 1, how consistently the token aligned with the name and
+    /// the name aligned with the token
+    pub confidence: f64,
+}
+
+/// Token names proposed by aligning a binary save with the same save in
+/// plaintext. See [`infer_tokens`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InferredTokens {
+    tokens: Vec<InferredToken>,
+}
+
+impl InferredTokens {
+    /// The proposed names, ordered by token id
+    pub fn tokens(&self) -> &[InferredToken] {
+        &self.tokens
+    }
+
+    /// Writes the names with at least the given confidence in the format
+    /// read by [`BasicTokenResolver::from_text_lines`](jomini::binary::BasicTokenResolver::from_text_lines)
+    pub fn write_text_lines<W: Write>(
+        &self,
+        mut writer: W,
+        min_confidence: f64,
+    ) -> Result<(), Hoi4Error> {
+        for token in &self.tokens {
+            if token.confidence >= min_confidence {
+                writeln!(writer, "0x{:04x} {}", token.id, token.name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Proposes names for the unknown tokens of a binary save by walking it
+/// alongside the same save in plaintext (eg: a save that was also exported
+/// with the game's text format). Both saves are given in full, including
+/// their headers.
+///
+/// Fields are aligned in order, and a token that takes the place of a key
+/// or an unquoted value in the text save gets a vote for that name. Tokens
+/// known by the resolver help keep the saves aligned and are not proposed.
+/// When the resolver does not know `save_version`, it is found by value as
+/// it decides how the rest of the binary save is decoded.
+pub fn infer_tokens<R>(binary: &[u8], text: &[u8], resolver: R) -> Result<InferredTokens, Hoi4Error>
+where
+    R: TokenResolver,
+{
+    let text = Hoi4ParsedText::from_slice(text)?;
+    let text_root = text.reader();
+    let save_version = text_root
+        .fields()
+        .find(|(key, _, _)| key.read_str() == "save_version")
+        .and_then(|(_, _, value)| value.read_scalar().ok())
+        .and_then(|x| x.to_i64().ok());
+
+    let save_version_id = file_header(binary)
+        .zip(save_version)
+        .and_then(|((_, body), version)| find_save_version(body, version, &resolver));
+    let resolver = SeededResolver {
+        inner: resolver,
+        save_version_id,
+    };
+
+    let binary = Hoi4ParsedBinary::from_slice(binary, resolver)?;
+    let mut aligner = Aligner {
+        resolver: binary.resolver(),
+        votes: HashMap::new(),
+    };
+    aligner.align_objects(binary.reader(), text_root);
+    Ok(aligner.finish())
+}
+
+/// Finds the id of an unknown root key whose i32 value is the save version.
+/// Only the scalars at the start of the save are considered, as the width
+/// of later values depends on the save version.
+fn find_save_version<R: TokenResolver>(body: &[u8], version: i64, resolver: &R) -> Option<u16> {
+    let mut lexer = Lexer::new(body);
+    let mut previous = None;
+    while let Ok(Some(token)) = lexer.read_token(false) {
+        match (previous, token) {
+            (_, BinaryToken::Open(_)) => return None,
+            (Some(BinaryToken::Id(id)), BinaryToken::Equal) if resolver.resolve(id).is_none() => {
+                if let Ok(Some(BinaryToken::I32(x))) = lexer.read_token(false) {
+                    if i64::from(x) == version {
+                        return Some(id);
+                    }
+                }
+                previous = None;
+                continue;
+            }
+            _ => {}
+        }
+        previous = Some(token);
+    }
+    None
+}
+
+/// Resolves the save version token found by value in addition to the
+/// tokens of the inner resolver
+struct SeededResolver<R> {
+    inner: R,
+    save_version_id: Option<u16>,
+}
+
+impl<R: TokenResolver> TokenResolver for SeededResolver<R> {
+    fn resolve(&self, token: u16) -> Option<&str> {
+        match self.inner.resolve(token) {
+            Some(name) => Some(name),
+            None if self.save_version_id == Some(token) => Some("save_version"),
+            None => None,
+        }
+    }
+}
+
+struct Aligner<'a, R> {
+    resolver: &'a SeededResolver<R>,
+    votes: HashMap<u16, HashMap<String, u32>>,
+}
+
+type BinaryObject<'doc, 'data, R> = binary::ObjectReader<'doc, 'data, R>;
+type BinaryValue<'doc, 'data, R> = binary::ValueReader<'doc, 'data, R>;
+type TextObject<'data, 'tokens> = text::ObjectReader<'data, 'tokens, Utf8Encoding>;
+type TextValue<'data, 'tokens> = text::ValueReader<'data, 'tokens, Utf8Encoding>;
+
+type BinaryKey<'doc, 'data, R> = binary::ScalarReader<'doc, 'data, SeededResolver<R>>;
+
+/// How a binary key compares to a text key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyMatch {
+    Unknown,
+    Equal,
+}
+
+impl<R: TokenResolver> Aligner<'_, R> {
+    fn unknown_id(&self, token: &BinaryToken) -> Option<u16> {
+        match *token {
+            // The save version is only seeded to decode the save, so it is
+            // still proposed
+            BinaryToken::Id(id) if self.resolver.inner.resolve(id).is_none() => Some(id),
+            _ => None,
+        }
+    }
+
+    fn vote(&mut self, id: u16, name: &str) {
+        let names = self.votes.entry(id).or_default();
+        *names.entry(name.to_string()).or_default() += 1;
+    }
+
+    /// Returns how the binary field lines up with the text field, or `None`
+    /// if they are not the same field
+    fn compare_fields(
+        &self,
+        (bkey, bvalue): (BinaryKey<'_, '_, R>, BinaryValue<'_, '_, SeededResolver<R>>),
+        (tkey, tvalue): (&str, &TextValue<'_, '_>),
+    ) -> Option<KeyMatch> {
+        let key = match *bkey.token() {
+            _ if self.unknown_id(bkey.token()).is_some() => KeyMatch::Unknown,
+            BinaryToken::I32(x) => {
+                let equal = x.to_string() == tkey
+                    || Hoi4Date::parse(tkey).is_ok_and(|date| date.to_binary() == x);
+                equal.then_some(KeyMatch::Equal)?
+            }
+            _ => (bkey.read_str() == tkey).then_some(KeyMatch::Equal)?,
+        };
+
+        self.values_compatible(bvalue, tvalue).then_some(key)
+    }
+
+    /// Returns true if the binary value could have been written as the text
+    /// value
+    fn values_compatible(
+        &self,
+        binary: BinaryValue<'_, '_, SeededResolver<R>>,
+        text: &TextValue<'_, '_>,
+    ) -> bool {
+        let scalar = match text.token() {
+            TextToken::Object { .. } | TextToken::Array { .. } => {
+                return matches!(binary.token(), BinaryToken::Open(_))
+            }
+            TextToken::Header(_) => return matches!(binary.token(), BinaryToken::Id(_)),
+            TextToken::Unquoted(scalar) | TextToken::Quoted(scalar) => *scalar,
+            _ => return false,
+        };
+
+        let approx = |x: f64| {
+            scalar
+                .to_f64()
+                .is_ok_and(|y| (x - y).abs() <= (y.abs() * 1e-3).max(1e-3))
+        };
+
+        match *binary.token() {
+            BinaryToken::Open(_) | BinaryToken::Close(_) | BinaryToken::Equal => false,
+            BinaryToken::Bool(x) => scalar.as_bytes() == if x { &b"yes"[..] } else { b"no" },
+            BinaryToken::I32(x) => {
+                scalar.to_i64() == Ok(i64::from(x))
+                    || Hoi4Date::parse(scalar.as_bytes()).is_ok_and(|date| date.to_binary() == x)
+            }
+            BinaryToken::U32(x) => scalar.to_u64() == Ok(u64::from(x)),
+            BinaryToken::U64(x) => scalar.to_u64() == Ok(x),
+            BinaryToken::I64(x) => scalar.to_i64() == Ok(x),
+            BinaryToken::F32(x) => approx(f64::from(x)),
+            BinaryToken::F64(x) => approx(x),
+
+            // Fixed point values are melted as whole numbers
+            BinaryToken::Fixed(x) => {
+                approx(binary::fixed_f64(x))
+                    || scalar
+                        .to_f64()
+                        .is_ok_and(|y| y.trunc() == binary::fixed_whole(x) as f64)
+            }
+            BinaryToken::Quoted(_) | BinaryToken::Unquoted(_) => binary
+                .read_str()
+                .is_some_and(|x| x.as_bytes() == scalar.as_bytes()),
+            BinaryToken::Id(_) if self.unknown_id(binary.token()).is_some() => {
+                matches!(text.token(), TextToken::Unquoted(_))
+            }
+            BinaryToken::Id(_) => binary
+                .read_str()
+                .is_some_and(|x| x.as_bytes() == scalar.as_bytes()),
+        }
+    }
+
+    fn align_objects(
+        &mut self,
+        binary: BinaryObject<'_, '_, SeededResolver<R>>,
+        text: TextObject<'_, '_>,
+    ) {
+        let binary: Vec<_> = binary.fields().collect();
+        let text: Vec<_> = text
+            .fields()
+            .map(|(key, _, value)| (key.read_str(), value))
+            .collect();
+
+        let (mut i, mut j) = (0, 0);
+        while let (Some(&bfield), Some((tkey, tvalue))) = (binary.get(i), text.get(j)) {
+            let Some(key) = self.compare_fields(bfield, (tkey, tvalue)) else {
+                // A field may be missing from one of the saves (eg: the
+                // ironman flag), so look ahead for where they line up
+                let text_ahead =
+                    text.iter()
+                        .skip(j + 1)
+                        .take(RESYNC_WINDOW)
+                        .position(|(tkey, tvalue)| {
+                            self.compare_fields(bfield, (tkey, tvalue)).is_some()
+                        });
+                let binary_ahead = binary
+                    .iter()
+                    .skip(i + 1)
+                    .take(RESYNC_WINDOW)
+                    .position(|&bfield| self.compare_fields(bfield, (tkey, tvalue)).is_some());
+
+                match (text_ahead, binary_ahead) {
+                    (Some(skip), _) => j += skip + 1,
+                    (None, Some(skip)) => i += skip + 1,
+                    (None, None) => {
+                        i += 1;
+                        j += 1;
+                    }
+                }
+                continue;
+            };
+
+            let (bkey, bvalue) = bfield;
+            if let (KeyMatch::Unknown, Some(id)) = (key, self.unknown_id(bkey.token())) {
+                self.vote(id, tkey);
+            }
+
+            self.align_values(bvalue, tvalue.clone());
+            i += 1;
+            j += 1;
+        }
+    }
+
+    fn align_values(
+        &mut self,
+        binary: BinaryValue<'_, '_, SeededResolver<R>>,
+        text: TextValue<'_, '_>,
+    ) {
+        match text.token() {
+            TextToken::Unquoted(scalar) | TextToken::Header(scalar) => {
+                if let Some(id) = self.unknown_id(binary.token()) {
+                    let name = scalar.to_string();
+                    self.vote(id, &name);
+                }
+            }
+            TextToken::Object { .. } => {
+                if let (Some(binary), Ok(text)) = (binary.read_object(), text.read_object()) {
+                    self.align_objects(binary, text);
+                }
+            }
+            TextToken::Array { .. } => {
+                let (Some(binary), Ok(text)) = (binary.read_array(), text.read_array()) else {
+                    return;
+                };
+
+                let binary: Vec<_> = binary.values().collect();
+                let text: Vec<_> = text.values().collect();
+                if binary.len() == text.len() {
+                    for (binary, text) in binary.into_iter().zip(text) {
+                        self.align_values(binary, text);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Picks the name with the most votes for each token. The confidence is
+    /// the share of the token's votes that went to the name, scaled by the
+    /// share of the name's votes that went to the token.
+    fn finish(self) -> InferredTokens {
+        let mut name_votes: HashMap<&str, u32> = HashMap::new();
+        for names in self.votes.values() {
+            for (name, votes) in names {
+                *name_votes.entry(name).or_default() += votes;
+            }
+        }
+
+        let mut tokens: Vec<_> = self
+            .votes
+            .iter()
+            .filter_map(|(&id, names)| {
+                let total: u32 = names.values().sum();
+                let (name, &votes) = names
+                    .iter()
+                    .max_by(|(a_name, a), (b_name, b)| a.cmp(b).then_with(|| b_name.cmp(a_name)))?;
+                let name_total = name_votes.get(name.as_str()).copied().unwrap_or(votes);
+                let confidence = (f64::from(votes) / f64::from(total))
+                    * (f64::from(votes) / f64::from(name_total));
+                Some(InferredToken {
+                    id,
+                    name: name.clone(),
+                    votes,
+                    confidence,
+                })
+            })
+            .collect();
+
+        tokens.sort_unstable_by_key(|token| token.id);
+        InferredTokens { tokens }
+    }
+}
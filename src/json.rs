@@ -0,0 +1,104 @@
+//! Options for converting saves to JSON
+
+use crate::KeyHints;
+pub use jomini::json::DuplicateKeyMode;
+
+/// How key value pairs inside of an array are represented, eg: the pairs in
+/// `version={ { player="GER" } a=1 b=2 }`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArrayObjectMode {
+    /// Each pair is an object with a single field:
+    /// `[{"player":"GER"},{"a":1},{"b":2}]`
+    #[default]
+    SingleField,
+
+    /// Consecutive pairs are merged into one object:
+    /// `[{"player":"GER"},{"a":1,"b":2}]`
+    Merge,
+}
+
+/// Customizes the JSON output
+#[derive(Debug, Clone)]
+pub struct JsonOptions {
+    pretty: bool,
+    duplicate_keys: DuplicateKeyMode,
+    array_objects: ArrayObjectMode,
+    hints: KeyHints,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonOptions {
+    /// Creates the structure with default options: minified output that
+    /// preserves duplicate keys and writes each key value pair inside of an
+    /// array as its own object
+    pub fn new() -> Self {
+        JsonOptions {
+            pretty: false,
+            duplicate_keys: DuplicateKeyMode::Preserve,
+            array_objects: ArrayObjectMode::SingleField,
+            hints: KeyHints::new(),
+        }
+    }
+
+    /// Sets if the JSON should be pretty printed or minified
+    pub fn with_prettyprint(self, pretty: bool) -> Self {
+        JsonOptions { pretty, ..self }
+    }
+
+    /// Sets how duplicate keys are formatted
+    pub fn with_duplicate_keys(self, duplicate_keys: DuplicateKeyMode) -> Self {
+        JsonOptions {
+            duplicate_keys,
+            ..self
+        }
+    }
+
+    /// Sets how key value pairs inside of an array are formatted
+    pub fn with_array_objects(self, array_objects: ArrayObjectMode) -> Self {
+        JsonOptions {
+            array_objects,
+            ..self
+        }
+    }
+
+    /// Sets how integers in binary saves are written, the same as when
+    /// melting
+    pub fn with_hints(self, hints: KeyHints) -> Self {
+        JsonOptions { hints, ..self }
+    }
+
+    pub(crate) fn pretty(&self) -> bool {
+        self.pretty
+    }
+
+    pub(crate) fn duplicate_keys(&self) -> DuplicateKeyMode {
+        self.duplicate_keys
+    }
+
+    pub(crate) fn array_objects(&self) -> ArrayObjectMode {
+        self.array_objects
+    }
+
+    pub(crate) fn hints(&self) -> &KeyHints {
+        &self.hints
+    }
+}
+
+pub(crate) fn writer_json<W, S>(writer: W, pretty: bool, ser: S) -> Result<(), std::io::Error>
+where
+    W: std::io::Write,
+    S: serde::Serialize,
+{
+    let result = if pretty {
+        serde_json::to_writer_pretty(writer, &ser)
+    } else {
+        serde_json::to_writer(writer, &ser)
+    };
+
+    result.map_err(|e| e.into())
+}
@@ -0,0 +1,778 @@
+//! Converts binary tokens to JSON. The same serializer writes parsed tokens
+//! and tokens read straight from a reader, so the two always agree.
+
+use crate::{
+    binary::{self, fixed_whole, next_value_index, payload, BinaryToken, Payload, RGB_ID},
+    flavor::Hoi4Flavor,
+    json::{writer_json, ArrayObjectMode, DuplicateKeyMode, JsonOptions},
+    melt::I32Hint,
+    reader::TokenReader,
+    Hoi4Date, Hoi4Error, Hoi4ErrorKind, PdsDate,
+};
+use jomini::{binary::TokenResolver, Encoding};
+use serde::{
+    ser::{Error, SerializeMap, SerializeSeq},
+    Serialize, Serializer,
+};
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+/// Writes the binary save body from the reader as JSON in a single pass over
+/// the token stream. Output matches converting a
+/// [`Hoi4ParsedBinary`](crate::file::Hoi4ParsedBinary) to JSON.
+///
+/// Grouping duplicate keys requires seeing every field of an object before
+/// writing it, so [`DuplicateKeyMode::Group`] reads the whole input into
+/// memory and parses it first.
+pub(crate) fn write_json<R, W, RES>(
+    mut input: R,
+    output: W,
+    resolver: RES,
+    options: JsonOptions,
+) -> Result<(), Hoi4Error>
+where
+    R: Read,
+    W: Write,
+    RES: TokenResolver,
+{
+    if options.duplicate_keys() == DuplicateKeyMode::Group {
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+        let tokens = binary::parse_tokens(&data, &resolver)?;
+        let json = Json::new(TreeSource::new(&tokens, &resolver), &options);
+        let result = writer_json(output, options.pretty(), json.root(Root::Object));
+        return json.finish(result);
+    }
+
+    let source = StreamSource {
+        reader: RefCell::new(TokenReader::new(input)),
+        resolver,
+        depth: Cell::new(0),
+        save_version_id: Cell::new(false),
+        new_save_format: Cell::new(false),
+    };
+    let json = Json::new(source, &options);
+    let result = writer_json(output, options.pretty(), json.root(Root::Object));
+    json.finish(result)
+}
+
+/// A binary token with any string decoded
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token<'a> {
+    Open,
+    Close,
+    Equal,
+    Bool(bool),
+    U32(u32),
+    U64(u64),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    Fixed(i64),
+    F64(f64),
+    Str(Cow<'a, str>),
+    Id(u16),
+}
+
+impl<'a> From<BinaryToken<'a>> for Token<'a> {
+    fn from(token: BinaryToken<'a>) -> Self {
+        match token {
+            BinaryToken::Open(_) => Token::Open,
+            BinaryToken::Close(_) => Token::Close,
+            BinaryToken::Equal => Token::Equal,
+            BinaryToken::Bool(x) => Token::Bool(x),
+            BinaryToken::U32(x) => Token::U32(x),
+            BinaryToken::U64(x) => Token::U64(x),
+            BinaryToken::I32(x) => Token::I32(x),
+            BinaryToken::I64(x) => Token::I64(x),
+            BinaryToken::F32(x) => Token::F32(x),
+            BinaryToken::Fixed(x) => Token::Fixed(x),
+            BinaryToken::F64(x) => Token::F64(x),
+            BinaryToken::Quoted(x) | BinaryToken::Unquoted(x) => Token::Str(Hoi4Flavor.decode(x)),
+            BinaryToken::Id(id) => Token::Id(id),
+        }
+    }
+}
+
+impl Token<'_> {
+    fn into_owned(self) -> Token<'static> {
+        match self {
+            Token::Open => Token::Open,
+            Token::Close => Token::Close,
+            Token::Equal => Token::Equal,
+            Token::Bool(x) => Token::Bool(x),
+            Token::U32(x) => Token::U32(x),
+            Token::U64(x) => Token::U64(x),
+            Token::I32(x) => Token::I32(x),
+            Token::I64(x) => Token::I64(x),
+            Token::F32(x) => Token::F32(x),
+            Token::Fixed(x) => Token::Fixed(x),
+            Token::F64(x) => Token::F64(x),
+            Token::Str(x) => Token::Str(Cow::Owned(x.into_owned())),
+            Token::Id(x) => Token::Id(x),
+        }
+    }
+}
+
+/// The kind of the upcoming token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Peek {
+    Open,
+    Close,
+    Equal,
+    Other,
+}
+
+/// Where the serializer reads binary tokens from, in order
+pub(crate) trait TokenSource {
+    type Resolver: TokenResolver;
+
+    fn resolver(&self) -> &Self::Resolver;
+
+    /// Reads the next token. Returns `None` at the end of input.
+    fn next_token(&self) -> Result<Option<Token<'_>>, Hoi4Error>;
+
+    /// The kind of the next token without consuming it. Returns `None` at
+    /// the end of input.
+    fn peek(&self) -> Result<Option<Peek>, Hoi4Error>;
+
+    /// Determines if the container that was just opened is an object: it is
+    /// either empty or its first element is followed by an equal operator
+    fn container_is_object(&self) -> Result<bool, Hoi4Error>;
+
+    /// The position of the next token when the source can later return to
+    /// it with [`TokenSource::rewind`]
+    fn checkpoint(&self) -> Option<usize>;
+
+    fn rewind(&self, position: usize);
+}
+
+/// Reads tokens that have already been parsed
+pub(crate) struct TreeSource<'doc, 'data, R> {
+    tokens: &'doc [BinaryToken<'data>],
+    resolver: &'doc R,
+    idx: Cell<usize>,
+    end: usize,
+}
+
+impl<'doc, 'data, R> TreeSource<'doc, 'data, R> {
+    pub(crate) fn new(tokens: &'doc [BinaryToken<'data>], resolver: &'doc R) -> Self {
+        Self::range(tokens, resolver, 0, tokens.len())
+    }
+
+    /// Reads the tokens from `start` up to, but excluding, `end`
+    pub(crate) fn range(
+        tokens: &'doc [BinaryToken<'data>],
+        resolver: &'doc R,
+        start: usize,
+        end: usize,
+    ) -> Self {
+        TreeSource {
+            tokens,
+            resolver,
+            idx: Cell::new(start),
+            end,
+        }
+    }
+
+    /// Reads the value at `start`, including the container of an `rgb`
+    pub(crate) fn value(
+        tokens: &'doc [BinaryToken<'data>],
+        resolver: &'doc R,
+        start: usize,
+    ) -> Self {
+        Self::range(tokens, resolver, start, next_value_index(tokens, start))
+    }
+
+    fn get(&self, idx: usize) -> Option<&BinaryToken<'data>> {
+        self.tokens[..self.end].get(idx)
+    }
+}
+
+impl<R: TokenResolver> TokenSource for TreeSource<'_, '_, R> {
+    type Resolver = R;
+
+    fn resolver(&self) -> &R {
+        self.resolver
+    }
+
+    fn next_token(&self) -> Result<Option<Token<'_>>, Hoi4Error> {
+        let idx = self.idx.get();
+        let token = self.get(idx).copied().map(Token::from);
+        self.idx.set(idx + usize::from(token.is_some()));
+        Ok(token)
+    }
+
+    fn peek(&self) -> Result<Option<Peek>, Hoi4Error> {
+        Ok(self.get(self.idx.get()).map(|token| match token {
+            BinaryToken::Open(_) => Peek::Open,
+            BinaryToken::Close(_) => Peek::Close,
+            BinaryToken::Equal => Peek::Equal,
+            _ => Peek::Other,
+        }))
+    }
+
+    fn container_is_object(&self) -> Result<bool, Hoi4Error> {
+        let idx = self.idx.get();
+        match self.get(idx) {
+            Some(BinaryToken::Close(_)) => Ok(true),
+            Some(BinaryToken::Open(_)) => Ok(false),
+            Some(_) => Ok(matches!(self.get(idx + 1), Some(BinaryToken::Equal))),
+            None => Err(Hoi4ErrorKind::Eof.into()),
+        }
+    }
+
+    fn checkpoint(&self) -> Option<usize> {
+        Some(self.idx.get())
+    }
+
+    fn rewind(&self, position: usize) {
+        self.idx.set(position);
+    }
+}
+
+/// Reads tokens from a reader as they are serialized
+struct StreamSource<R, RES> {
+    reader: RefCell<TokenReader<R>>,
+    resolver: RES,
+    depth: Cell<usize>,
+    save_version_id: Cell<bool>,
+    new_save_format: Cell<bool>,
+}
+
+impl<R, RES> TokenSource for StreamSource<R, RES>
+where
+    R: Read,
+    RES: TokenResolver,
+{
+    type Resolver = RES;
+
+    fn resolver(&self) -> &RES {
+        &self.resolver
+    }
+
+    fn next_token(&self) -> Result<Option<Token<'_>>, Hoi4Error> {
+        let mut reader = self.reader.borrow_mut();
+        let offset = reader.position();
+        let Some(token) = reader.read_token(self.new_save_format.get())? else {
+            return Ok(None);
+        };
+
+        match token {
+            BinaryToken::Open(_) => self.depth.set(self.depth.get() + 1),
+            BinaryToken::Close(_) => match self.depth.get().checked_sub(1) {
+                Some(depth) => self.depth.set(depth),
+                None => return Err(Hoi4ErrorKind::UnbalancedContainers { offset }.into()),
+            },
+            BinaryToken::I32(x) if self.save_version_id.get() => {
+                self.new_save_format.set(x >= 30);
+            }
+            BinaryToken::Id(id) => {
+                if let Some(name) = self.resolver.resolve(id) {
+                    self.save_version_id.set(name == "save_version");
+                }
+            }
+            _ => {}
+        }
+
+        // Strings are copied out of the read buffer
+        Ok(Some(Token::from(token).into_owned()))
+    }
+
+    fn peek(&self) -> Result<Option<Peek>, Hoi4Error> {
+        let mut reader = self.reader.borrow_mut();
+        Ok(match *reader.peek(2)? {
+            [0x03, 0x00] => Some(Peek::Open),
+            [0x04, 0x00] => Some(Peek::Close),
+            [0x01, 0x00] => Some(Peek::Equal),
+            [_, _] => Some(Peek::Other),
+            _ => None,
+        })
+    }
+
+    fn container_is_object(&self) -> Result<bool, Hoi4Error> {
+        let mut reader = self.reader.borrow_mut();
+        let id = match *reader.peek(2)? {
+            [a, b] => u16::from_le_bytes([a, b]),
+            _ => return Err(Hoi4ErrorKind::Eof.into()),
+        };
+
+        let len = match id {
+            0x0004 => return Ok(true),
+            0x0003 => return Ok(false),
+            id => match payload(id, self.new_save_format.get()) {
+                Payload::None => 0,
+                Payload::Fixed(len) => len,
+                Payload::String => match *reader.peek(4)? {
+                    [_, _, a, b] => 2 + usize::from(u16::from_le_bytes([a, b])),
+                    _ => return Err(Hoi4ErrorKind::Eof.into()),
+                },
+            },
+        };
+
+        let ahead = reader.peek(2 + len + 2)?;
+        Ok(ahead.get(2 + len..) == Some(&[0x01, 0x00][..]))
+    }
+
+    fn checkpoint(&self) -> Option<usize> {
+        None
+    }
+
+    fn rewind(&self, _position: usize) {}
+}
+
+/// What is serialized from the start of a source
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Root {
+    /// The fields of an object that ends at the end of the source
+    Object,
+
+    /// The values of an array that ends at the end of the source
+    Array,
+
+    /// A single value whose integers are written with the hint
+    Value(I32Hint),
+}
+
+/// Serializes the tokens of a source as JSON
+pub(crate) struct Json<'o, Src> {
+    source: Src,
+    options: &'o JsonOptions,
+    error: RefCell<Option<Hoi4Error>>,
+}
+
+impl<'o, Src: TokenSource> Json<'o, Src> {
+    pub(crate) fn new(source: Src, options: &'o JsonOptions) -> Self {
+        Json {
+            source,
+            options,
+            error: RefCell::new(None),
+        }
+    }
+
+    pub(crate) fn root(&self, root: Root) -> JsonRoot<'_, 'o, Src> {
+        JsonRoot { json: self, root }
+    }
+
+    /// Returns the error recorded while serializing, if any, instead of the
+    /// serializer's stringified version
+    fn finish(self, result: Result<(), std::io::Error>) -> Result<(), Hoi4Error> {
+        match (self.error.into_inner(), result) {
+            (Some(e), _) => Err(e),
+            (None, result) => Ok(result?),
+        }
+    }
+
+    fn fail<E: Error>(&self, err: Hoi4Error) -> E {
+        let msg = err.to_string();
+        self.error.borrow_mut().get_or_insert(err);
+        E::custom(msg)
+    }
+
+    fn expect_token(&self) -> Result<Token<'_>, Hoi4Error> {
+        self.source
+            .next_token()?
+            .ok_or_else(|| Hoi4ErrorKind::Eof.into())
+    }
+
+    /// Consumes the next token if it is an equal operator
+    fn next_is_equal(&self) -> Result<bool, Hoi4Error> {
+        if self.source.peek()? == Some(Peek::Equal) {
+            self.expect_token()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Consumes the container of an `rgb` header, if the token is one
+    fn is_rgb(&self, token: &Token) -> Result<bool, Hoi4Error> {
+        if *token == Token::Id(RGB_ID) && self.source.peek()? == Some(Peek::Open) {
+            self.expect_token()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Skips the remainder of a container that has been opened
+    fn skip_container(&self) -> Result<(), Hoi4Error> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.expect_token()? {
+                Token::Open => depth += 1,
+                Token::Close => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Skips the rest of a value whose first token has been read
+    fn skip_value(&self, token: &Token) -> Result<(), Hoi4Error> {
+        if *token == Token::Open || self.is_rgb(token)? {
+            self.skip_container()?;
+        }
+        Ok(())
+    }
+
+    fn key_str<'a>(&'a self, token: &Token<'a>) -> Cow<'a, str> {
+        match token {
+            Token::Str(x) => x.clone(),
+            Token::Id(id) => match self.source.resolver().resolve(*id) {
+                Some(name) => Cow::Borrowed(name),
+                None => Cow::Owned(format!("__unknown_0x{:x}", id)),
+            },
+            Token::Bool(x) => Cow::Borrowed(if *x { "yes" } else { "no" }),
+            Token::U32(x) => Cow::Owned(x.to_string()),
+            Token::U64(x) => Cow::Owned(x.to_string()),
+            Token::I32(x) => Cow::Owned(x.to_string()),
+            Token::I64(x) => Cow::Owned(x.to_string()),
+            Token::F32(x) => Cow::Owned(x.to_string()),
+            Token::Fixed(x) => Cow::Owned(fixed_whole(*x).to_string()),
+            Token::F64(x) => Cow::Owned(x.to_string()),
+            Token::Open | Token::Close | Token::Equal => Cow::Borrowed(""),
+        }
+    }
+
+    fn hint(&self, key: &Token) -> I32Hint {
+        match key {
+            Token::Id(id) => self
+                .source
+                .resolver()
+                .resolve(*id)
+                .map_or(I32Hint::Heuristic, |key| self.options.hints().hint(key)),
+            _ => I32Hint::Heuristic,
+        }
+    }
+
+    /// Reads the next key value pair of an object, skipping elements that
+    /// are not part of one. Returns `None` at the end of the object, which
+    /// for the root is the end of input.
+    fn next_field(&self, root: bool) -> Result<Option<(Token<'_>, Token<'_>)>, Hoi4Error> {
+        loop {
+            let key = match self.source.next_token()? {
+                None if root => return Ok(None),
+                None => return Err(Hoi4ErrorKind::Eof.into()),
+                Some(Token::Close) => return Ok(None),
+                Some(token) => token,
+            };
+
+            if key == Token::Open {
+                self.skip_container()?;
+                continue;
+            }
+
+            if !self.next_is_equal()? {
+                continue;
+            }
+
+            return match self.expect_token()? {
+                Token::Close => Ok(None),
+                value => Ok(Some((key, value))),
+            };
+        }
+    }
+
+    /// Reads the value of a key value pair inside of an array if the token
+    /// is followed by an equal operator
+    fn array_field<'a>(
+        &'a self,
+        token: Token<'a>,
+    ) -> Result<Result<(Token<'a>, Token<'a>), Token<'a>>, Hoi4Error> {
+        if matches!(token, Token::Open | Token::Close) || !self.next_is_equal()? {
+            return Ok(Err(token));
+        }
+
+        let value = self.expect_token()?;
+        Ok(Ok((token, value)))
+    }
+}
+
+pub(crate) struct JsonRoot<'j, 'o, Src> {
+    json: &'j Json<'o, Src>,
+    root: Root,
+}
+
+impl<Src: TokenSource> Serialize for JsonRoot<'_, '_, Src> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let json = self.json;
+        match self.root {
+            Root::Object => JsonObject { json, root: true }.serialize(serializer),
+            Root::Array => JsonArray { json, root: true }.serialize(serializer),
+            Root::Value(hint) => match json.source.next_token().map_err(|e| json.fail(e))? {
+                Some(token) => JsonValue { json, token, hint }.serialize(serializer),
+                None => serializer.serialize_none(),
+            },
+        }
+    }
+}
+
+/// The fields of an object that are read as they are serialized
+struct JsonObject<'j, 'o, Src> {
+    json: &'j Json<'o, Src>,
+    root: bool,
+}
+
+impl<Src: TokenSource> Serialize for JsonObject<'_, '_, Src> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let json = self.json;
+        match json.options.duplicate_keys() {
+            DuplicateKeyMode::Preserve => {
+                let mut map = serializer.serialize_map(None)?;
+                while let Some((key, token)) = self.next_field().map_err(|e| json.fail(e))? {
+                    let hint = json.hint(&key);
+                    map.serialize_entry(&json.key_str(&key), &JsonValue { json, token, hint })?;
+                }
+                map.end()
+            }
+            DuplicateKeyMode::Group => self.serialize_groups(serializer),
+            DuplicateKeyMode::KeyValuePairs => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("type", "obj")?;
+                map.serialize_entry("val", &JsonPairs(self))?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'j, Src: TokenSource> JsonObject<'j, '_, Src> {
+    fn next_field(&self) -> Result<Option<(Token<'j>, Token<'j>)>, Hoi4Error> {
+        self.json.next_field(self.root)
+    }
+
+    /// Writes the values of duplicate keys as an array. Every field has to
+    /// be seen before the object is written, so each value is skipped and
+    /// then returned to.
+    fn serialize_groups<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let json = self.json;
+        let mut groups: Vec<(Cow<str>, Vec<GroupValue<Src>>)> = Vec::new();
+        let mut positions: HashMap<Cow<str>, usize> = HashMap::new();
+        while let Some((key, token)) = self.next_field().map_err(|e| json.fail(e))? {
+            let Some(position) = json.source.checkpoint() else {
+                let msg = String::from("grouping duplicate keys requires parsed tokens");
+                return Err(json.fail(Hoi4ErrorKind::InvalidValue { msg }.into()));
+            };
+
+            json.skip_value(&token).map_err(|e| json.fail(e))?;
+            let value = GroupValue {
+                json,
+                hint: json.hint(&key),
+                token,
+                position,
+            };
+
+            let key = json.key_str(&key);
+            match positions.get(&key) {
+                Some(&idx) => groups[idx].1.push(value),
+                None => {
+                    positions.insert(key.clone(), groups.len());
+                    groups.push((key, vec![value]));
+                }
+            }
+        }
+
+        let end = json.source.checkpoint();
+        let mut map = serializer.serialize_map(Some(groups.len()))?;
+        for (key, values) in &groups {
+            match values.as_slice() {
+                [value] => map.serialize_entry(key, value)?,
+                values => map.serialize_entry(key, values)?,
+            }
+        }
+
+        if let Some(end) = end {
+            json.source.rewind(end);
+        }
+        map.end()
+    }
+}
+
+/// A value of a grouped key that is returned to when serialized
+struct GroupValue<'j, 'o, Src> {
+    json: &'j Json<'o, Src>,
+    token: Token<'j>,
+    hint: I32Hint,
+    position: usize,
+}
+
+impl<Src: TokenSource> Serialize for GroupValue<'_, '_, Src> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.json.source.rewind(self.position);
+        let value = JsonValue {
+            json: self.json,
+            token: self.token.clone(),
+            hint: self.hint,
+        };
+        value.serialize(serializer)
+    }
+}
+
+struct JsonPairs<'a, 'j, 'o, Src>(&'a JsonObject<'j, 'o, Src>);
+
+impl<Src: TokenSource> Serialize for JsonPairs<'_, '_, '_, Src> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let json = self.0.json;
+        let mut seq = serializer.serialize_seq(None)?;
+        while let Some((key, token)) = self.0.next_field().map_err(|e| json.fail(e))? {
+            let hint = json.hint(&key);
+            seq.serialize_element(&(json.key_str(&key), JsonValue { json, token, hint }))?;
+        }
+        seq.end()
+    }
+}
+
+/// A value whose first token has been read. Containers are read as they
+/// are serialized, so a value must be serialized exactly once.
+struct JsonValue<'j, 'o, Src> {
+    json: &'j Json<'o, Src>,
+    token: Token<'j>,
+    hint: I32Hint,
+}
+
+impl<Src: TokenSource> Serialize for JsonValue<'_, '_, Src> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let json = self.json;
+        match self.token {
+            Token::Open => {
+                if json
+                    .source
+                    .container_is_object()
+                    .map_err(|e| json.fail(e))?
+                {
+                    JsonObject { json, root: false }.serialize(serializer)
+                } else {
+                    JsonArray { json, root: false }.serialize(serializer)
+                }
+            }
+            Token::Bool(x) => serializer.serialize_bool(x),
+            Token::U32(x) => serializer.serialize_u32(x),
+            Token::U64(x) => serializer.serialize_u64(x),
+            Token::I64(x) => serializer.serialize_i64(x),
+            Token::I32(x) => {
+                let date = match self.hint {
+                    I32Hint::Number => None,
+                    I32Hint::Date => Hoi4Date::from_binary(x),
+                    I32Hint::Heuristic => Hoi4Date::from_binary_heuristic(x),
+                };
+
+                match date {
+                    Some(date) => serializer.collect_str(&date.game_fmt()),
+                    None => serializer.serialize_i32(x),
+                }
+            }
+            Token::F32(x) => serializer.serialize_f32(x),
+            Token::Fixed(x) => serializer.serialize_i64(fixed_whole(x)),
+            Token::F64(x) => serializer.serialize_f64(x),
+            Token::Str(_) | Token::Id(_) => {
+                if json.is_rgb(&self.token).map_err(|e| json.fail(e))? {
+                    let mut map = serializer.serialize_map(Some(1))?;
+                    map.serialize_entry("rgb", &JsonArray { json, root: false })?;
+                    map.end()
+                } else {
+                    serializer.serialize_str(&json.key_str(&self.token))
+                }
+            }
+            Token::Close | Token::Equal => serializer.serialize_none(),
+        }
+    }
+}
+
+/// The values of an array that are read as they are serialized
+struct JsonArray<'j, 'o, Src> {
+    json: &'j Json<'o, Src>,
+    root: bool,
+}
+
+impl<Src: TokenSource> Serialize for JsonArray<'_, '_, Src> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.json.options.duplicate_keys() == DuplicateKeyMode::KeyValuePairs {
+            let mut map = serializer.serialize_map(Some(2))?;
+            map.serialize_entry("type", "array")?;
+            map.serialize_entry("val", &JsonArrayValues(self))?;
+            map.end()
+        } else {
+            JsonArrayValues(self).serialize(serializer)
+        }
+    }
+}
+
+struct JsonArrayValues<'a, 'j, 'o, Src>(&'a JsonArray<'j, 'o, Src>);
+
+impl<Src: TokenSource> Serialize for JsonArrayValues<'_, '_, '_, Src> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let json = self.0.json;
+        let pending = RefCell::new(None);
+        let mut seq = serializer.serialize_seq(None)?;
+        loop {
+            let token = match pending.take() {
+                Some(token) => token,
+                None => match json.source.next_token().map_err(|e| json.fail(e))? {
+                    Some(token) => token,
+                    None if self.0.root => break,
+                    None => return Err(json.fail(Hoi4ErrorKind::Eof.into())),
+                },
+            };
+
+            if token == Token::Close {
+                break;
+            }
+
+            // A key value pair inside an array is written as an object
+            match json.array_field(token).map_err(|e| json.fail(e))? {
+                Ok(field) => seq.serialize_element(&ArrayFields {
+                    json,
+                    first: RefCell::new(Some(field)),
+                    pending: &pending,
+                })?,
+                Err(token) => seq.serialize_element(&JsonValue {
+                    json,
+                    token,
+                    hint: I32Hint::Heuristic,
+                })?,
+            }
+        }
+        seq.end()
+    }
+}
+
+/// Key value pairs inside of an array written as one object. When merging
+/// consecutive pairs, the token that ends the run is left in `pending`.
+struct ArrayFields<'a, 'j, 'o, Src> {
+    json: &'j Json<'o, Src>,
+    first: RefCell<Option<(Token<'j>, Token<'j>)>>,
+    pending: &'a RefCell<Option<Token<'j>>>,
+}
+
+impl<Src: TokenSource> Serialize for ArrayFields<'_, '_, '_, Src> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let json = self.json;
+        let mut map = serializer.serialize_map(None)?;
+        let mut field = self.first.borrow_mut().take();
+        while let Some((key, token)) = field {
+            let hint = json.hint(&key);
+            map.serialize_entry(&json.key_str(&key), &JsonValue { json, token, hint })?;
+
+            if json.options.array_objects() != ArrayObjectMode::Merge {
+                break;
+            }
+
+            field = match json.source.next_token().map_err(|e| json.fail(e))? {
+                Some(token) => match json.array_field(token).map_err(|e| json.fail(e))? {
+                    Ok(field) => Some(field),
+                    Err(token) => {
+                        *self.pending.borrow_mut() = Some(token);
+                        None
+                    }
+                },
+                None => None,
+            };
+        }
+        map.end()
+    }
+}
@@ -35,27 +35,70 @@ let out = binary
 # Ok::<(), Box<dyn std::error::Error>>(())
 ```
 
+## Compressed Saves
+
+With the `compression` feature enabled, saves that have been zipped, gzipped,
+or zlib compressed are detected and unwrapped.
+
+```rust,ignore
+use hoi4save::{Container, Hoi4File};
+
+let data = std::fs::read("assets/saves/1.10-normal-text.zip")?;
+let mut buf = Vec::new();
+let file = Hoi4File::from_compressed_slice(&data, &mut buf)?;
+assert_eq!(file.container(), Container::Zip);
+# Ok::<(), Box<dyn std::error::Error>>(())
+```
+
 ## Binary Saves
 
 Binary saves are supported, but not by default, as the token resolver can't be distributed, per PDS counsel.
 
+A token file can be located with `TokenLoader`, which checks the
+`HOI4_IRONMAN_TOKENS` environment variable, a given path, and then
+`hoi4save/hoi4.txt` in the user's config directory.
+
 */
 
+#[cfg(feature = "tokio")]
+pub mod async_file;
+pub mod binary;
+#[cfg(feature = "compression")]
+mod compression;
 mod country_tag;
 mod date;
 mod de;
+mod edit;
 mod errors;
 mod extraction;
 pub mod file;
 mod flavor;
+mod freeze;
+mod infer;
+pub mod json;
+mod json_stream;
 mod melt;
 pub mod models;
+mod path;
+mod reader;
+mod report;
+mod ser;
+mod summary;
+mod tokens;
 
+#[cfg(feature = "compression")]
+pub use compression::{Decompressor, DEFAULT_DECOMPRESSED_LIMIT};
 pub use country_tag::*;
 pub use date::*;
 pub use errors::*;
 pub use extraction::*;
 #[doc(inline)]
 pub use file::Hoi4File;
+pub use freeze::*;
+pub use infer::*;
 pub use jomini::binary::{BasicTokenResolver, FailedResolveStrategy};
 pub use melt::*;
+pub use report::*;
+pub use ser::*;
+pub use summary::*;
+pub use tokens::*;
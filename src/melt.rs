@@ -1,18 +1,29 @@
-use crate::{flavor::Hoi4Flavor, Hoi4Date, Hoi4Error, Hoi4ErrorKind};
+use crate::{
+    binary::{decode_token, fixed_whole, payload, resolve_str, BinaryToken, Payload, RGB_ID},
+    reader::TokenReader,
+    ErrorLocation, Hoi4Date, Hoi4Error, Hoi4ErrorKind,
+};
 use jomini::{
-    binary::{BinaryFlavor, FailedResolveStrategy, TokenResolver},
+    binary::{FailedResolveStrategy, TokenResolver},
     common::PdsDate,
     TextWriterBuilder,
 };
 use std::{
-    collections::HashSet,
+    borrow::Cow,
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
     io::{Read, Write},
+    sync::Arc,
 };
 
 /// Output from melting a binary save to plaintext
 #[derive(Debug, Default)]
 pub struct MeltedDocument {
     unknown_tokens: HashSet<u16>,
+    source_map: Vec<SourceMapping>,
+    statistics: Option<MeltStatistics>,
+    unknown_token_context: HashMap<u16, UnknownTokenContext>,
+    truncation: Option<Truncation>,
 }
 
 impl MeltedDocument {
@@ -24,12 +35,373 @@ impl MeltedDocument {
     pub fn unknown_tokens(&self) -> &HashSet<u16> {
         &self.unknown_tokens
     }
+
+    /// The text written for each binary token, in output order. Empty unless
+    /// requested with [`MeltOptions::source_map`].
+    pub fn source_map(&self) -> &[SourceMapping] {
+        &self.source_map
+    }
+
+    /// Counts gathered while melting, if requested with
+    /// [`MeltOptions::statistics`]
+    pub fn statistics(&self) -> Option<&MeltStatistics> {
+        self.statistics.as_ref()
+    }
+
+    /// Where each unknown token appeared. Empty unless requested with
+    /// [`MeltOptions::unknown_token_context`].
+    pub fn unknown_token_context(&self) -> &HashMap<u16, UnknownTokenContext> {
+        &self.unknown_token_context
+    }
+
+    /// Where the input ended if it was cut short and melted with
+    /// [`MeltOptions::recover`]
+    pub fn truncation(&self) -> Option<&Truncation> {
+        self.truncation.as_ref()
+    }
+}
+
+/// Where a save that was cut short ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Truncation {
+    /// Byte offset into the save body of the first incomplete token or
+    /// field
+    pub offset: usize,
+
+    /// Dot separated keys of the field being melted when the input ended.
+    /// The root object is the empty path.
+    pub path: String,
+
+    /// The number of containers that were left open and closed in the
+    /// output
+    pub closed: usize,
+}
+
+/// Where an unknown token appeared in a save, to help infer its name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnknownTokenContext {
+    /// Dot separated key paths of the fields that the token appeared
+    /// within. The root object is the empty path.
+    pub paths: BTreeSet<String>,
+
+    /// The number of times the token was a key
+    pub key_count: u64,
+
+    /// The number of times the token was a value
+    pub value_count: u64,
+
+    /// The token ids of the values that followed the token as a key (eg:
+    /// `0x000c` for an i32 or `0x0003` for an object or array)
+    pub value_types: BTreeSet<u16>,
+
+    /// The first scalar value that followed the token as a key
+    pub sample_value: Option<String>,
+}
+
+/// Counts of what was encountered while melting a save
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MeltStatistics {
+    /// The number of times each token id was melted. This includes value
+    /// types (eg: `0x000c` for i32s and `0x0167` for f64s) and operators.
+    /// Tokens that are not written, because they are dropped by a filter or
+    /// are the ironman flag, are not counted.
+    pub token_counts: HashMap<u16, u64>,
+
+    /// The number of i32 values written as a date because their key is a
+    /// known date
+    pub known_dates: u64,
+
+    /// The number of i32 values written as a date because they fall in a
+    /// plausible date range
+    pub heuristic_dates: u64,
+
+    /// The value of the `save_version` field, if one was encountered
+    pub save_version: Option<i32>,
+
+    /// If `0x000d` values were decoded as the 8 byte fixed point numbers of
+    /// newer saves
+    pub new_save_format: bool,
+}
+
+/// Relates melted text to the binary token that it was written from
+///
+/// Offsets are relative to the save bodies: the binary data that follows
+/// the `HOI4bin` header and the text that follows the `HOI4txt` line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceMapping {
+    /// Byte offset of the start of the text written for the token, not
+    /// including leading whitespace
+    pub output_start: usize,
+
+    /// Byte offset of the end of the text written for the token
+    pub output_end: usize,
+
+    /// The one based line of the melted body that the text starts on
+    pub line: usize,
+
+    /// Byte offset of the token id in the binary data
+    pub input_offset: usize,
+
+    /// The binary token id
+    pub token_id: u16,
+}
+
+/// The keys of the containers being melted and of the field being melted.
+/// Containers without a key (eg: objects in an array) are `None`.
+#[derive(Debug, Default)]
+struct KeyPath {
+    parents: Vec<Option<String>>,
+    key: String,
+    has_key: bool,
+}
+
+impl KeyPath {
+    fn parents(&self) -> impl Iterator<Item = &str> {
+        self.parents.iter().flatten().map(String::as_str)
+    }
+
+    fn key(&self) -> Option<&str> {
+        self.has_key.then_some(self.key.as_str())
+    }
+
+    fn set_key(&mut self, key: &str) {
+        self.key.clear();
+        self.key.push_str(key);
+        self.has_key = true;
+    }
+
+    fn clear_key(&mut self) {
+        self.has_key = false;
+    }
+
+    fn open(&mut self) {
+        let key = self.key().map(String::from);
+        self.parents.push(key);
+        self.has_key = false;
+    }
+
+    fn close(&mut self) {
+        self.parents.pop();
+        self.has_key = false;
+    }
+
+    /// The dot separated keys of the parents and, if given, the field
+    fn join(&self, with_key: bool) -> String {
+        let mut keys: Vec<&str> = self.parents().collect();
+        if with_key {
+            keys.extend(self.key());
+        }
+        keys.join(".")
+    }
+}
+
+/// The length of the payload that follows the token id
+fn payload_len<R: Read>(
+    reader: &mut TokenReader<R>,
+    id: u16,
+    new_save_format: bool,
+) -> Result<usize, Hoi4Error> {
+    match payload(id, new_save_format) {
+        Payload::None => Ok(0),
+        Payload::Fixed(len) => Ok(len),
+        Payload::String => match *reader.peek(2)? {
+            [a, b] => Ok(2 + usize::from(u16::from_le_bytes([a, b]))),
+            _ => Err(Hoi4ErrorKind::Eof.into()),
+        },
+    }
+}
+
+/// Records the key for the token if it is followed by an equal operator
+fn peek_key<R: Read, RES: TokenResolver>(
+    reader: &mut TokenReader<R>,
+    id: u16,
+    new_save_format: bool,
+    resolver: &RES,
+    keys: &mut KeyPath,
+) -> Result<bool, Hoi4Error> {
+    if matches!(id, 0x0001 | 0x0003 | 0x0004) {
+        return Ok(false);
+    }
+
+    let len = payload_len(reader, id, new_save_format)?;
+    let ahead = reader.peek(len + 2)?;
+    if ahead.get(len..) != Some(&[0x01, 0x00][..]) {
+        return Ok(false);
+    }
+
+    let data = match payload(id, new_save_format) {
+        Payload::String => &ahead[2..len],
+        _ => &ahead[..len],
+    };
+    let token = decode_token(id, data);
+    keys.set_key(&resolve_str(resolver, &token).unwrap_or_default());
+    Ok(true)
+}
+
+/// Returns true if the rest of the key, its equal operator, and the start of
+/// its value are available to be read
+fn field_available<R: Read>(
+    reader: &mut TokenReader<R>,
+    id: u16,
+    new_save_format: bool,
+) -> Result<bool, Hoi4Error> {
+    let start = payload_len(reader, id, new_save_format)? + 4;
+    let Some(&[a, b]) = reader.peek(start)?.get(start - 2..) else {
+        return Ok(false);
+    };
+
+    let len = match payload(u16::from_le_bytes([a, b]), new_save_format) {
+        Payload::None => 0,
+        Payload::Fixed(len) => len,
+        Payload::String => match reader.peek(start + 2)?.get(start..) {
+            Some(&[a, b]) => 2 + usize::from(u16::from_le_bytes([a, b])),
+            _ => return Ok(false),
+        },
+    };
+
+    Ok(reader.peek(start + len)?.len() == start + len)
+}
+
+/// Walks the tokens of a binary save body, tracking their keys, until `stop`
+/// returns true for a token's offset, whether it is a key, and the keys
+/// leading up to it. Returns the offset and keys of that token, or the keys
+/// where the input ended or could not be read.
+fn walk_keys<RES: TokenResolver>(
+    data: &[u8],
+    resolver: &RES,
+    mut stop: impl FnMut(usize, bool, &KeyPath) -> bool,
+) -> Result<(usize, KeyPath), KeyPath> {
+    let mut reader = TokenReader::new(data);
+    let mut keys = KeyPath::default();
+    let mut new_save_format = false;
+    loop {
+        let offset = reader.position();
+        let Ok(Some(id)) = reader.read_id() else {
+            return Err(keys);
+        };
+
+        let Ok(is_key) = peek_key(&mut reader, id, new_save_format, resolver, &mut keys) else {
+            return Err(keys);
+        };
+
+        if stop(offset, is_key, &keys) {
+            return Ok((offset, keys));
+        }
+
+        let read = match id {
+            0x0001 => Ok(()),
+            0x0003 => {
+                keys.open();
+                Ok(())
+            }
+            0x0004 => {
+                keys.close();
+                Ok(())
+            }
+            0x000c if keys.key() == Some("save_version") => reader
+                .read_array()
+                .map(|x| new_save_format = i32::from_le_bytes(x) >= 30),
+            _ => skip_payload(&mut reader, id, new_save_format),
+        };
+
+        if read.is_err() {
+            return Err(keys);
+        }
+
+        if !is_key && !matches!(id, 0x0001 | 0x0003 | 0x0004) {
+            keys.clear_key();
+        }
+    }
+}
+
+/// Returns the byte offset of the first field at the dot separated key path
+/// in a binary save body
+pub(crate) fn locate_field<RES: TokenResolver>(
+    data: &[u8],
+    resolver: &RES,
+    path: &str,
+) -> Option<usize> {
+    walk_keys(data, resolver, |_, is_key, keys| {
+        is_key && keys.join(true) == path
+    })
+    .ok()
+    .map(|(offset, _)| offset)
+}
+
+/// Returns the dot separated key path of the token at the byte offset in a
+/// binary save body, as it would be tracked while melting
+pub(crate) fn locate_path<RES: TokenResolver>(
+    data: &[u8],
+    resolver: &RES,
+    offset: usize,
+) -> String {
+    match walk_keys(data, resolver, |at, _, _| at >= offset) {
+        Ok((_, keys)) | Err(keys) => keys.join(true),
+    }
+}
+
+/// Counts the bytes and lines written to the melted output so that tokens
+/// can be mapped to where they were written
+struct CountingWriter<W> {
+    inner: W,
+    written: usize,
+    lines: usize,
+    enabled: bool,
+
+    /// Offset and line of the first non-whitespace byte since the last mark
+    content: Option<(usize, usize)>,
+}
+
+impl<W> CountingWriter<W> {
+    fn mark(&mut self) {
+        self.content = None;
+    }
+
+    /// Returns the mapping for the token if any text was written for it
+    /// since the last mark
+    fn mapping(&self, input_offset: usize, token_id: u16) -> Option<SourceMapping> {
+        let (output_start, line) = self.content?;
+        Some(SourceMapping {
+            output_start,
+            output_end: self.written,
+            line: line + 1,
+            input_offset,
+            token_id,
+        })
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        if self.enabled {
+            for (i, &c) in buf[..len].iter().enumerate() {
+                if self.content.is_none() && !c.is_ascii_whitespace() {
+                    self.content = Some((self.written + i, self.lines));
+                }
+                if c == b'\n' {
+                    self.lines += 1;
+                }
+            }
+        }
+        self.written += len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct MeltOptions {
     verbatim: bool,
     on_failed_resolve: FailedResolveStrategy,
+    source_map: bool,
+    statistics: bool,
+    unknown_token_context: bool,
+    recover: bool,
+    strict: bool,
 }
 
 impl Default for MeltOptions {
@@ -43,6 +415,11 @@ impl MeltOptions {
         Self {
             verbatim: false,
             on_failed_resolve: FailedResolveStrategy::Ignore,
+            source_map: false,
+            statistics: false,
+            unknown_token_context: false,
+            recover: false,
+            strict: false,
         }
     }
 
@@ -56,189 +433,660 @@ impl MeltOptions {
             ..self
         }
     }
+
+    /// Records where the text for each binary token was written. See
+    /// [`MeltedDocument::source_map`].
+    pub fn source_map(self, source_map: bool) -> Self {
+        MeltOptions { source_map, ..self }
+    }
+
+    /// Gathers counts of the tokens and values that are melted. See
+    /// [`MeltedDocument::statistics`].
+    pub fn statistics(self, statistics: bool) -> Self {
+        MeltOptions { statistics, ..self }
+    }
+
+    /// Records the key paths, value types and a sample value for each
+    /// unknown token. See [`MeltedDocument::unknown_token_context`].
+    pub fn unknown_token_context(self, unknown_token_context: bool) -> Self {
+        MeltOptions {
+            unknown_token_context,
+            ..self
+        }
+    }
+
+    /// Melts as much of a save that was cut short as possible instead of
+    /// returning an [`Eof`](crate::Hoi4ErrorKind::Eof) error. Fields are only
+    /// written once their value has started, open containers are closed, and
+    /// where the input ended is reported with [`MeltedDocument::truncation`].
+    pub fn recover(self, recover: bool) -> Self {
+        MeltOptions { recover, ..self }
+    }
+
+    /// Checks that open and close tokens are balanced: a close token without
+    /// a matching open token is an
+    /// [`UnbalancedContainers`](crate::Hoi4ErrorKind::UnbalancedContainers)
+    /// error and a save that ends with containers still open is an
+    /// [`Eof`](crate::Hoi4ErrorKind::Eof) error. Off by default, where
+    /// unclosed containers are melted as is.
+    pub fn strict(self, strict: bool) -> Self {
+        MeltOptions { strict, ..self }
+    }
+}
+
+type I32HintFn = dyn Fn(&str) -> Option<I32Hint> + Send + Sync;
+
+/// Decides how the integer value of a key is written when a binary save is
+/// melted or converted to JSON. The keys and callback given here are
+/// checked before the defaults, where `date` is a date and `total`,
+/// `available`, `locked` and any key that ends in `seed` are numbers.
+#[derive(Clone, Default)]
+pub struct KeyHints {
+    known_numbers: HashSet<String>,
+    known_dates: HashSet<String>,
+    i32_hint: Option<Arc<I32HintFn>>,
+}
+
+impl fmt::Debug for KeyHints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyHints")
+            .field("known_numbers", &self.known_numbers)
+            .field("known_dates", &self.known_dates)
+            .field("i32_hint", &self.i32_hint.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl KeyHints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds keys whose integer values are always written as numbers
+    pub fn known_numbers<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.known_numbers.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Adds keys whose integer values are always written as dates
+    pub fn known_dates<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.known_dates.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets a callback that decides how the integer value of a key is
+    /// written. Returning `None` falls back to the known number and date
+    /// keys.
+    ///
+    /// ```rust
+    /// use hoi4save::{I32Hint, KeyHints};
+    /// let hints = KeyHints::new().i32_hint(|key| {
+    ///     key.starts_with("mod_counter").then_some(I32Hint::Number)
+    /// });
+    /// assert_eq!(hints.hint("mod_counter_a"), I32Hint::Number);
+    /// assert_eq!(hints.hint("date"), I32Hint::Date);
+    /// ```
+    pub fn i32_hint<F>(self, hint: F) -> Self
+    where
+        F: Fn(&str) -> Option<I32Hint> + Send + Sync + 'static,
+    {
+        KeyHints {
+            i32_hint: Some(Arc::new(hint)),
+            ..self
+        }
+    }
+
+    /// Decides how the integer value of the key is written: by the
+    /// callback, then the known number and date keys, then the defaults
+    pub fn hint(&self, key: &str) -> I32Hint {
+        if let Some(hint) = self.i32_hint.as_ref().and_then(|f| f(key)) {
+            hint
+        } else if self.known_numbers.contains(key) {
+            I32Hint::Number
+        } else if self.known_dates.contains(key) {
+            I32Hint::Date
+        } else {
+            default_hint(key)
+        }
+    }
+}
+
+/// [`MeltOptions`] along with the [`KeyHints`] and key path filters to melt
+/// with. Melting accepts either, so options on their own melt with the
+/// default hints and without filters.
+#[derive(Debug, Clone, Default)]
+pub struct MeltConfig {
+    options: MeltOptions,
+    hints: KeyHints,
+    filter: PathFilter,
+}
+
+impl MeltConfig {
+    pub fn new(options: MeltOptions) -> Self {
+        MeltConfig {
+            options,
+            hints: KeyHints::default(),
+            filter: PathFilter::default(),
+        }
+    }
+
+    pub fn hints(self, hints: KeyHints) -> Self {
+        MeltConfig { hints, ..self }
+    }
+
+    /// Only melt the fields at the dot separated key path (eg:
+    /// `countries.*.variables`), where `*` matches any key. The fields
+    /// leading up to the path are melted, but none of their siblings. May
+    /// be called multiple times to keep several paths.
+    pub fn include(mut self, path: &str) -> Self {
+        self.filter
+            .include
+            .push(path.split('.').map(String::from).collect());
+        self
+    }
+
+    /// Drops the fields at the dot separated key path, where `*` matches any
+    /// key. Takes precedence over [`MeltConfig::include`]. Dropped values
+    /// are skipped without resolving their tokens.
+    pub fn exclude(mut self, path: &str) -> Self {
+        self.filter
+            .exclude
+            .push(path.split('.').map(String::from).collect());
+        self
+    }
+}
+
+/// The key paths to keep or drop when melting
+#[derive(Debug, Clone, Default)]
+struct PathFilter {
+    include: Vec<Vec<String>>,
+    exclude: Vec<Vec<String>>,
+}
+
+impl PathFilter {
+    fn has_filters(&self) -> bool {
+        !self.include.is_empty() || !self.exclude.is_empty()
+    }
+
+    /// Returns true if the field at the end of the key path should be
+    /// melted
+    fn keep_field(&self, keys: &KeyPath) -> bool {
+        let path: Vec<&str> = keys.parents().chain(keys.key()).collect();
+
+        let matches = |pattern: &Vec<String>| {
+            pattern
+                .iter()
+                .zip(&path)
+                .all(|(segment, key)| segment == "*" || segment == key)
+        };
+
+        if self
+            .exclude
+            .iter()
+            .any(|pattern| pattern.len() <= path.len() && matches(pattern))
+        {
+            return false;
+        }
+
+        // A field is kept if it is within an included path or on the way to
+        // one
+        self.include.is_empty() || self.include.iter().any(matches)
+    }
+}
+
+impl From<MeltOptions> for MeltConfig {
+    fn from(options: MeltOptions) -> Self {
+        MeltConfig::new(options)
+    }
+}
+
+/// How the integer value of a key is written when melted. Binary saves
+/// encode dates as integers, so without a hint an integer is written as a
+/// date only if it is in a plausible date range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I32Hint {
+    /// Always written as a number
+    Number,
+
+    /// Always written as a date
+    Date,
+
+    /// Written as a date if it looks like one
+    Heuristic,
+}
+
+/// Skips the payload that follows the token id
+fn skip_payload<R: Read>(
+    reader: &mut TokenReader<R>,
+    id: u16,
+    new_save_format: bool,
+) -> Result<(), Hoi4Error> {
+    reader.read_payload(id, new_save_format).map(|_| ())
+}
+
+/// Skips the rest of a container that has been opened
+fn skip_container<R: Read>(
+    reader: &mut TokenReader<R>,
+    new_save_format: bool,
+) -> Result<(), Hoi4Error> {
+    let mut depth = 1;
+    while depth > 0 {
+        match reader.read_id()?.ok_or(Hoi4ErrorKind::Eof)? {
+            0x0003 => depth += 1,
+            0x0004 => depth -= 1,
+            id => skip_payload(reader, id, new_save_format)?,
+        }
+    }
+    Ok(())
+}
+
+/// Skips the value of a field that has been filtered out. The save version
+/// is still read, and returned, as it determines how later tokens are
+/// decoded.
+fn skip_value<R: Read>(
+    reader: &mut TokenReader<R>,
+    new_save_format: &mut bool,
+    save_version: bool,
+) -> Result<Option<i32>, Hoi4Error> {
+    match reader.read_id()?.ok_or(Hoi4ErrorKind::Eof)? {
+        0x0003 => skip_container(reader, *new_save_format)?,
+        0x000c if save_version => {
+            let x = i32::from_le_bytes(reader.read_array()?);
+            *new_save_format = x >= 30;
+            return Ok(Some(x));
+        }
+
+        // An rgb value is followed by its channels
+        RGB_ID if reader.peek(2)? == [0x03, 0x00] => {
+            reader.skip(2)?;
+            skip_container(reader, *new_save_format)?;
+        }
+        id => skip_payload(reader, id, *new_save_format)?,
+    }
+
+    Ok(None)
+}
+
+/// Peeks at the value that follows a key and its equal operator, returning
+/// the value's token id and its text if it is a scalar
+fn peek_field_value<R: Read, RES: TokenResolver>(
+    reader: &mut TokenReader<R>,
+    resolver: &RES,
+    new_save_format: bool,
+) -> Result<Option<(u16, Option<String>)>, Hoi4Error> {
+    let [_, _, a, b] = *reader.peek(4)? else {
+        return Ok(None);
+    };
+
+    let id = u16::from_le_bytes([a, b]);
+    let (start, len) = match payload(id, new_save_format) {
+        Payload::None => (4, 0),
+        Payload::Fixed(len) => (4, len),
+        Payload::String => match *reader.peek(6)? {
+            [_, _, _, _, a, b] => (6, usize::from(u16::from_le_bytes([a, b]))),
+            _ => return Ok(Some((id, None))),
+        },
+    };
+
+    let ahead = reader.peek(start + len)?;
+    let sample = ahead
+        .get(start..)
+        .filter(|data| data.len() == len)
+        .and_then(|data| resolve_str(resolver, &decode_token(id, data)))
+        .map(Cow::into_owned);
+    Ok(Some((id, sample)))
+}
+
+/// The default hint for a key
+fn default_hint(key: &str) -> I32Hint {
+    if key.ends_with("seed") || matches!(key, "total" | "available" | "locked") {
+        I32Hint::Number
+    } else if key == "date" {
+        I32Hint::Date
+    } else {
+        I32Hint::Heuristic
+    }
 }
 
 pub(crate) fn melt<Reader, Writer, Resolver>(
-    mut input: Reader,
+    input: Reader,
     output: Writer,
     resolver: Resolver,
-    options: MeltOptions,
+    config: MeltConfig,
+) -> Result<MeltedDocument, Hoi4Error>
+where
+    Reader: Read,
+    Writer: Write,
+    Resolver: TokenResolver,
+{
+    melt_located(input, output, resolver, config, |_, _| None)
+}
+
+/// Melts a binary save body that is in memory, so that the key path of an
+/// error can be found after the fact without tracking keys while melting
+pub(crate) fn melt_slice<Writer, Resolver>(
+    data: &[u8],
+    output: Writer,
+    resolver: Resolver,
+    config: MeltConfig,
+) -> Result<MeltedDocument, Hoi4Error>
+where
+    Writer: Write,
+    Resolver: TokenResolver,
+{
+    melt_located(data, output, resolver, config, |resolver, offset| {
+        Some(locate_path(data, resolver, offset))
+    })
+}
+
+/// Melts the input. Keys are only tracked when an option needs them, so
+/// otherwise `locate` is given the offset of an error to find its key path.
+fn melt_located<Reader, Writer, Resolver>(
+    input: Reader,
+    output: Writer,
+    resolver: Resolver,
+    config: MeltConfig,
+    locate: impl FnOnce(&Resolver, usize) -> Option<String>,
 ) -> Result<MeltedDocument, Hoi4Error>
 where
     Reader: Read,
     Writer: Write,
     Resolver: TokenResolver,
 {
-    let mut buffer = Vec::new();
-    input.read_to_end(&mut buffer)?;
-    let mut data = buffer.as_slice();
+    let MeltConfig {
+        options,
+        hints,
+        filter,
+    } = config;
+    let mut reader = TokenReader::new(input);
     let mut save_version_id = false;
     let mut new_save_format = false;
 
     let mut unknown_tokens = HashSet::new();
-    let flavor = Hoi4Flavor;
 
     let mut wtr = TextWriterBuilder::new()
         .indent_char(b'\t')
         .indent_factor(1)
-        .from_writer(output);
+        .from_writer(CountingWriter {
+            inner: output,
+            written: 0,
+            lines: 0,
+            enabled: options.source_map,
+            content: None,
+        });
+    let mut source_map = Vec::new();
+    let mut stats = options.statistics.then(MeltStatistics::default);
+    let mut buffered_offset = 0;
 
     let mut known_number = false;
     let mut known_date = false;
     let mut quoted_buffer_enabled = false;
     let mut quoted_buffer: Vec<u8> = Vec::new();
 
-    while !data.is_empty() {
-        let (id, rest) = data.split_first_chunk::<2>().ok_or(Hoi4ErrorKind::Eof)?;
-        let id = u16::from_le_bytes(*id);
-        data = rest;
+    // The keys of the containers being melted, only tracked when filtering
+    // by path, recording unknown tokens, or recovering a save cut short
+    let filtering = filter.has_filters();
+    let track_keys = filtering || options.unknown_token_context || options.recover;
+    let mut unknown_token_context: HashMap<u16, UnknownTokenContext> = HashMap::new();
+    let mut keys = KeyPath::default();
+    let mut depth = 0;
+    let mut offset = 0;
 
-        if quoted_buffer_enabled {
-            if matches!(id, 0x0001) {
-                wtr.write_unquoted(&quoted_buffer)?;
-            } else {
-                wtr.write_quoted(&quoted_buffer)?;
+    // Errors are reported with the offset and key path of the token being
+    // melted
+    let result = (|| -> Result<(), Hoi4Error> {
+        loop {
+            offset = reader.position();
+            let Some(id) = reader.read_id()? else {
+                // Containers left open mean the save was cut short
+                if depth > 0 && (options.strict || options.recover) {
+                    return Err(Hoi4ErrorKind::Eof.into());
+                }
+                break;
+            };
+
+            if quoted_buffer_enabled {
+                wtr.inner().mark();
+                if matches!(id, 0x0001) {
+                    wtr.write_unquoted(&quoted_buffer)?;
+                } else {
+                    wtr.write_quoted(&quoted_buffer)?;
+                }
+                quoted_buffer.clear();
+                quoted_buffer_enabled = false;
+
+                if options.source_map {
+                    source_map.extend(wtr.inner().mapping(buffered_offset, 0x000f));
+                }
             }
-            quoted_buffer.clear();
-            quoted_buffer_enabled = false;
-        }
-
-        match id {
-            0x0001 => wtr.write_operator(jomini::text::Operator::Equal)?,
-            0x0003 => wtr.write_start()?,
-            0x0004 => wtr.write_end()?,
-            0x0014 => {
-                let (id, rest) = data.split_first_chunk::<4>().ok_or(Hoi4ErrorKind::Eof)?;
-                let val = u32::from_le_bytes(*id);
-                data = rest;
-                wtr.write_u32(val)?
+
+            let is_key =
+                track_keys && peek_key(&mut reader, id, new_save_format, &resolver, &mut keys)?;
+            if is_key && filtering && !filter.keep_field(&keys) {
+                let len = payload_len(&mut reader, id, new_save_format)?;
+                reader.skip(len + 2)?;
+                let save_version = keys.key() == Some("save_version");
+                let version = skip_value(&mut reader, &mut new_save_format, save_version)?;
+                if let (Some(stats), Some(version)) = (stats.as_mut(), version) {
+                    stats.save_version = Some(version);
+                }
+                keys.clear_key();
+                continue;
+            }
+
+            // A field is only written once its value has started so that a
+            // save cut short can be closed off
+            if is_key && options.recover && !field_available(&mut reader, id, new_save_format)? {
+                return Err(Hoi4ErrorKind::Eof.into());
             }
-            0x029c => {
-                let (id, rest) = data.split_first_chunk::<8>().ok_or(Hoi4ErrorKind::Eof)?;
-                let val = u64::from_le_bytes(*id);
-                data = rest;
-                wtr.write_u64(val)?
+
+            let unknown = options.unknown_token_context
+                && payload(id, new_save_format) == Payload::None
+                && !matches!(id, 0x0001 | 0x0003 | 0x0004)
+                && resolver.resolve(id).is_none();
+            if unknown {
+                let context = unknown_token_context.entry(id).or_default();
+                if is_key {
+                    context.key_count += 1;
+                    if let Some((value_id, sample)) =
+                        peek_field_value(&mut reader, &resolver, new_save_format)?
+                    {
+                        context.value_types.insert(value_id);
+                        if context.sample_value.is_none() {
+                            context.sample_value = sample;
+                        }
+                    }
+                    context.paths.insert(keys.join(false));
+                } else {
+                    context.value_count += 1;
+                    context.paths.insert(keys.join(true));
+                }
             }
-            0x000c => {
-                let (id, rest) = data.split_first_chunk::<4>().ok_or(Hoi4ErrorKind::Eof)?;
-                let x = i32::from_le_bytes(*id);
-                data = rest;
-
-                if save_version_id {
-                    new_save_format = x >= 30;
-                    wtr.write_i32(x)?;
-                } else if known_number {
-                    wtr.write_i32(x)?;
-                    known_number = false;
-                } else if known_date {
-                    if let Some(date) = Hoi4Date::from_binary(x) {
+
+            wtr.inner().mark();
+            match decode_token(id, reader.read_payload(id, new_save_format)?) {
+                BinaryToken::Equal => wtr.write_operator(jomini::text::Operator::Equal)?,
+                BinaryToken::Open(_) => wtr.write_start()?,
+                BinaryToken::Close(_) if depth == 0 && options.strict => {
+                    return Err(Hoi4ErrorKind::UnbalancedContainers { offset }.into());
+                }
+                BinaryToken::Close(_) => wtr.write_end()?,
+                BinaryToken::U32(x) => wtr.write_u32(x)?,
+                BinaryToken::U64(x) => wtr.write_u64(x)?,
+                BinaryToken::I32(x) => {
+                    if save_version_id {
+                        new_save_format = x >= 30;
+                        if let Some(stats) = stats.as_mut() {
+                            stats.save_version = Some(x);
+                        }
+                        wtr.write_i32(x)?;
+                    } else if known_number {
+                        wtr.write_i32(x)?;
+                        known_number = false;
+                    } else if known_date {
+                        if let Some(date) = Hoi4Date::from_binary(x) {
+                            if let Some(stats) = stats.as_mut() {
+                                stats.known_dates += 1;
+                            }
+                            wtr.write_date(date.game_fmt())?;
+                        } else if options.on_failed_resolve != FailedResolveStrategy::Error {
+                            wtr.write_i32(x)?;
+                        } else {
+                            return Err(Hoi4Error::from(Hoi4ErrorKind::InvalidDate(x)));
+                        }
+                        known_date = false;
+                    } else if let Some(date) = Hoi4Date::from_binary_heuristic(x) {
+                        if let Some(stats) = stats.as_mut() {
+                            stats.heuristic_dates += 1;
+                        }
                         wtr.write_date(date.game_fmt())?;
-                    } else if options.on_failed_resolve != FailedResolveStrategy::Error {
+                    } else {
                         wtr.write_i32(x)?;
+                    }
+                }
+                BinaryToken::Bool(x) => wtr.write_bool(x)?,
+                BinaryToken::Unquoted(x) => wtr.write_unquoted(x)?,
+                BinaryToken::Quoted(x) => {
+                    if wtr.at_unknown_start() {
+                        buffered_offset = offset;
+                        quoted_buffer_enabled = true;
+                        quoted_buffer.extend_from_slice(x);
+                    } else if wtr.expecting_key() {
+                        wtr.write_unquoted(x)?;
                     } else {
-                        return Err(Hoi4Error::from(Hoi4ErrorKind::InvalidDate(x)));
+                        wtr.write_quoted(x)?;
                     }
-                    known_date = false;
-                } else if let Some(date) = Hoi4Date::from_binary_heuristic(x) {
-                    wtr.write_date(date.game_fmt())?;
-                } else {
-                    wtr.write_i32(x)?;
                 }
+                BinaryToken::Fixed(x) => wtr.write_i64(fixed_whole(x))?,
+                BinaryToken::F32(x) => wtr.write_f32(x)?,
+                BinaryToken::F64(x) => wtr.write_f64(x)?,
+                BinaryToken::I64(x) => wtr.write_i64(x)?,
+                BinaryToken::Id(id) => match resolver.resolve(id) {
+                    Some(id) => {
+                        if !options.verbatim
+                            && matches!(id, "is_ironman" | "ironman")
+                            && wtr.expecting_key()
+                        {
+                            // skip equals
+                            let ahead = reader.peek(6)?;
+                            let (_, value) = ahead.split_at_checked(2).ok_or(Hoi4ErrorKind::Eof)?;
+                            let (token_id, _) =
+                                value.split_first_chunk::<2>().ok_or(Hoi4ErrorKind::Eof)?;
+
+                            match u16::from_le_bytes(*token_id) {
+                                // skip i32
+                                0x000c => {
+                                    reader.skip(8)?;
+                                    continue;
+                                }
+                                0x000f => {
+                                    // get str len
+                                    let (len, _) = ahead[4..]
+                                        .split_first_chunk::<2>()
+                                        .ok_or(Hoi4ErrorKind::Eof)?;
+                                    let len = usize::from(u16::from_le_bytes(*len));
+                                    reader.skip(6 + len)?;
+                                    continue;
+                                }
+                                _ => {}
+                            }
+                        }
+
+                        let hint = hints.hint(id);
+                        known_number = hint == I32Hint::Number;
+                        known_date = hint == I32Hint::Date;
+                        save_version_id = id == "save_version";
+                        wtr.write_unquoted(id.as_bytes())?;
+                    }
+                    None => match options.on_failed_resolve {
+                        FailedResolveStrategy::Error => {
+                            return Err(Hoi4ErrorKind::UnknownToken { token_id: id }.into());
+                        }
+                        _ => {
+                            unknown_tokens.insert(id);
+                            write!(wtr, "__unknown_0x{:x}", id)?;
+                        }
+                    },
+                },
             }
-            0x000e => {
-                let (id, rest) = data.split_first().ok_or(Hoi4ErrorKind::Eof)?;
-                data = rest;
-                wtr.write_bool(*id != 0)?
+
+            if let Some(stats) = stats.as_mut() {
+                *stats.token_counts.entry(id).or_default() += 1;
             }
-            0x000f | 0x0017 => {
-                let (len, rest) = data.split_first_chunk::<2>().ok_or(Hoi4ErrorKind::Eof)?;
-                let len = u16::from_le_bytes(*len);
-                let (x, rest) = rest
-                    .split_at_checked(len as usize)
-                    .ok_or(Hoi4ErrorKind::Eof)?;
-                data = rest;
-                if id == 0x0017 {
-                    wtr.write_unquoted(x)?;
-                } else if wtr.at_unknown_start() {
-                    quoted_buffer_enabled = true;
-                    quoted_buffer.extend_from_slice(x);
-                } else if wtr.expecting_key() {
-                    wtr.write_unquoted(x)?;
-                } else {
-                    wtr.write_quoted(x)?;
-                }
+
+            if options.source_map {
+                source_map.extend(wtr.inner().mapping(offset, id));
             }
-            0x000d => {
-                if new_save_format {
-                    let (id, rest) = data.split_first_chunk::<8>().ok_or(Hoi4ErrorKind::Eof)?;
-                    let val = i64::from_le_bytes(*id);
-                    data = rest;
-                    wtr.write_i64(val / 100000)?
-                } else {
-                    let (id, rest) = data.split_first_chunk::<4>().ok_or(Hoi4ErrorKind::Eof)?;
-                    let val = flavor.visit_f32(*id);
-                    data = rest;
-                    wtr.write_f32(val)?
+
+            match id {
+                0x0003 => depth += 1,
+                0x0004 => depth -= 1,
+                _ => {}
+            }
+
+            if track_keys {
+                match id {
+                    0x0001 => {}
+                    0x0003 => keys.open(),
+                    0x0004 => keys.close(),
+                    _ if !is_key => keys.clear_key(),
+                    _ => {}
                 }
             }
-            0x0167 => {
-                let (id, rest) = data.split_first_chunk::<8>().ok_or(Hoi4ErrorKind::Eof)?;
-                let val = flavor.visit_f64(*id);
-                data = rest;
-                wtr.write_f64(val)?
+        }
+        Ok(())
+    })();
+
+    let truncation = match result {
+        Err(err) if options.recover && matches!(err.kind(), Hoi4ErrorKind::Eof) => {
+            if quoted_buffer_enabled {
+                wtr.write_quoted(&quoted_buffer)?;
             }
-            0x0317 => {
-                let (id, rest) = data.split_first_chunk::<8>().ok_or(Hoi4ErrorKind::Eof)?;
-                let val = i64::from_le_bytes(*id);
-                data = rest;
-                wtr.write_i64(val)?
+
+            let closed = depth;
+            for _ in 0..closed {
+                wtr.write_end()?;
             }
-            id => match resolver.resolve(id) {
-                Some(id) => {
-                    if !options.verbatim
-                        && matches!(id, "is_ironman" | "ironman")
-                        && wtr.expecting_key()
-                    {
-                        // skip equals
-                        let rest = data.get(2..).ok_or(Hoi4ErrorKind::Eof)?;
-
-                        let (token_id, rest) =
-                            rest.split_first_chunk::<2>().ok_or(Hoi4ErrorKind::Eof)?;
-                        let id = u16::from_le_bytes(*token_id);
-
-                        // skip i32
-                        if id == 0x000c {
-                            data = rest.get(4..).ok_or(Hoi4ErrorKind::Eof)?;
-                            continue;
-                        } else if id == 0x000f {
-                            // get str len
-                            let (len, rest) =
-                                rest.split_first_chunk::<2>().ok_or(Hoi4ErrorKind::Eof)?;
-                            let len = u16::from_le_bytes(*len);
-
-                            data = rest.get(len as usize..).ok_or(Hoi4ErrorKind::Eof)?;
-                            continue;
-                        }
-                    }
 
-                    known_number =
-                        id.ends_with("seed") || matches!(id, "total" | "available" | "locked");
-                    known_date = id == "date";
-                    save_version_id = id == "save_version";
-                    wtr.write_unquoted(id.as_bytes())?;
-                }
-                None => match options.on_failed_resolve {
-                    FailedResolveStrategy::Error => {
-                        return Err(Hoi4ErrorKind::UnknownToken { token_id: id }.into());
-                    }
-                    _ => {
-                        unknown_tokens.insert(id);
-                        write!(wtr, "__unknown_0x{:x}", id)?;
-                    }
-                },
-            },
+            Some(Truncation {
+                offset,
+                path: keys.join(true),
+                closed,
+            })
+        }
+        result => {
+            result.map_err(|err| {
+                let path = if track_keys {
+                    Some(keys.join(true))
+                } else {
+                    locate(&resolver, offset)
+                };
+                err.with_location(ErrorLocation {
+                    offset: Some(offset),
+                    path,
+                })
+            })?;
+            None
         }
+    };
+
+    if let Some(stats) = stats.as_mut() {
+        stats.new_save_format = new_save_format;
     }
 
-    Ok(MeltedDocument { unknown_tokens })
+    Ok(MeltedDocument {
+        unknown_tokens,
+        source_map,
+        statistics: stats,
+        unknown_token_context,
+        truncation,
+    })
 }
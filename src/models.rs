@@ -1,5 +1,5 @@
 use crate::{
-    de::{deserialize_hashmap_f64, deserialize_vec_pair},
+    de::{deserialize_color, deserialize_hashmap_f64, deserialize_vec_pair},
     CountryTag, Hoi4Date,
 };
 use jomini::JominiDeserialize;
@@ -9,9 +9,36 @@ use std::collections::HashMap;
 #[derive(JominiDeserialize, Debug, Clone, Serialize)]
 pub struct Hoi4Save {
     pub player: String,
+    #[serde(serialize_with = "crate::serialize_date")]
     pub date: Hoi4Date,
     #[jomini(default, deserialize_with = "deserialize_vec_pair")]
     pub countries: Vec<(CountryTag, Country)>,
+    #[jomini(default, alias = "civil_war", duplicated)]
+    #[serde(rename = "civil_war")]
+    pub civil_wars: Vec<CivilWar>,
+}
+
+impl Hoi4Save {
+    /// Returns the country data for the given tag
+    pub fn country(&self, tag: &CountryTag) -> Option<&Country> {
+        self.countries
+            .iter()
+            .find(|(t, _)| t == tag)
+            .map(|(_, country)| country)
+    }
+
+    /// Maps a dynamic tag (eg: `D01`) back to the tag of the country it
+    /// represents. Tags that are not dynamic, or whose original tag is not
+    /// recorded, are returned as is.
+    pub fn original_tag(&self, tag: CountryTag) -> CountryTag {
+        if !tag.is_dynamic() {
+            return tag;
+        }
+
+        self.country(&tag)
+            .and_then(|country| country.original_tag)
+            .unwrap_or(tag)
+    }
 }
 
 #[derive(JominiDeserialize, Debug, Clone, Serialize)]
@@ -22,4 +49,95 @@ pub struct Country {
     pub war_support: f64,
     #[jomini(default, deserialize_with = "deserialize_hashmap_f64")]
     pub variables: HashMap<String, f64>,
+
+    /// For dynamic countries (eg: `D01`), the tag of the country they represent
+    pub original_tag: Option<CountryTag>,
+
+    /// Cosmetic tag that overrides the country's name and flag
+    pub cosmetic_tag: Option<String>,
+
+    /// Map color of the country. Empty when the save does not record one.
+    #[jomini(default, deserialize_with = "deserialize_color")]
+    pub color: Vec<f64>,
+
+    /// Collaboration level of each country that is collaborating with this one
+    #[jomini(default, deserialize_with = "deserialize_vec_pair")]
+    pub collaboration: Vec<(CountryTag, f64)>,
+
+    /// Set when the country is a collaboration government installed by
+    /// another country
+    pub collaboration_government: Option<CollaborationGovernment>,
+
+    /// Balance of power bars. Absent in saves that predate the mechanic.
+    #[jomini(default, alias = "power_balance", duplicated)]
+    #[serde(rename = "power_balance")]
+    pub power_balances: Vec<PowerBalance>,
+
+    /// Special projects. Absent in saves that predate the mechanic.
+    #[jomini(default, alias = "special_project", duplicated)]
+    #[serde(rename = "special_project")]
+    pub special_projects: Vec<SpecialProject>,
+}
+
+/// A civil war between a country and the country that revolted against it
+#[derive(JominiDeserialize, Debug, Clone, Serialize)]
+pub struct CivilWar {
+    pub original: CountryTag,
+    pub revolter: CountryTag,
+    pub ideology: Option<String>,
+    #[serde(serialize_with = "crate::serialize_date_opt")]
+    pub date: Option<Hoi4Date>,
+}
+
+/// The country that installed a collaboration government
+#[derive(JominiDeserialize, Debug, Clone, Serialize)]
+pub struct CollaborationGovernment {
+    pub master: CountryTag,
+    #[serde(serialize_with = "crate::serialize_date_opt")]
+    pub date: Option<Hoi4Date>,
+}
+
+/// A balance of power bar that swings between two sides
+#[derive(JominiDeserialize, Debug, Clone, Serialize)]
+pub struct PowerBalance {
+    pub id: String,
+
+    /// Position of the bar, from -1 (fully left) to 1 (fully right)
+    #[jomini(default)]
+    pub value: f64,
+    pub left_side: Option<String>,
+    pub right_side: Option<String>,
+
+    /// The range that the value currently falls within
+    pub active_range: Option<String>,
+
+    /// Weekly change applied to the value
+    #[jomini(default)]
+    pub change: f64,
+}
+
+/// A special project assigned to a research facility
+#[derive(JominiDeserialize, Debug, Clone, Serialize)]
+pub struct SpecialProject {
+    pub id: String,
+
+    /// The state where the project's facility is located
+    pub facility: Option<u32>,
+
+    /// The scientist currently assigned to the project
+    pub scientist: Option<Hoi4Id>,
+
+    #[jomini(default)]
+    pub progress: f64,
+    #[jomini(default)]
+    pub complete: bool,
+}
+
+/// An identifier that references another entity in the save (eg: a character)
+#[derive(JominiDeserialize, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Hoi4Id {
+    pub id: i32,
+    #[jomini(alias = "type")]
+    #[serde(rename = "type")]
+    pub kind: i32,
 }
@@ -0,0 +1,328 @@
+use serde::de::{self, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess, Visitor};
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    fmt::Write,
+};
+
+/// The dot separated keys of the field being deserialized. A key is only
+/// removed once its value has been deserialized, so after an error the path
+/// is of the field that failed.
+#[derive(Debug, Default)]
+pub(crate) struct KeyTrail {
+    path: RefCell<String>,
+
+    /// The length of the path for each field being deserialized
+    lens: RefCell<Vec<usize>>,
+
+    /// Whether a key, rather than a value, is being deserialized
+    in_key: Cell<bool>,
+}
+
+impl KeyTrail {
+    pub(crate) fn path(&self) -> String {
+        self.path.borrow().clone()
+    }
+
+    /// The path of the token being deserialized: the enclosing object for
+    /// a key and the field for a value
+    pub(crate) fn token_path(&self) -> String {
+        let path = self.path.borrow();
+        if self.in_key.get() {
+            let len = self.lens.borrow().last().copied().unwrap_or(0);
+            path[..len].to_string()
+        } else {
+            path.clone()
+        }
+    }
+
+    fn start_key(&self) -> std::cell::RefMut<'_, String> {
+        let mut path = self.path.borrow_mut();
+        path.truncate(self.lens.borrow().last().copied().unwrap_or(0));
+        if !path.is_empty() {
+            path.push('.');
+        }
+        path
+    }
+
+    fn push(&self) {
+        let len = self.path.borrow().len();
+        self.lens.borrow_mut().push(len);
+    }
+
+    fn pop(&self) {
+        let mut lens = self.lens.borrow_mut();
+        lens.pop();
+        let len = lens.last().copied().unwrap_or(0);
+        self.path.borrow_mut().truncate(len);
+    }
+}
+
+/// A deserializer that records the keys of the fields it deserializes
+pub(crate) struct Tracked<'a, D> {
+    de: D,
+    trail: &'a KeyTrail,
+}
+
+impl<'a, D> Tracked<'a, D> {
+    pub(crate) fn new(de: D, trail: &'a KeyTrail) -> Self {
+        Tracked { de, trail }
+    }
+}
+
+/// Forwards each deserialize method to the inner deserializer with the
+/// visitor wrapped
+macro_rules! forward_deserialize {
+    ($wrap:ident; $($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, D::Error>
+            where
+                V: Visitor<'de>,
+            {
+                let visitor = $wrap {
+                    visitor,
+                    trail: self.trail,
+                };
+                self.de.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+macro_rules! deserializer_impl {
+    ($name:ident, $wrap:ident) => {
+        impl<'de, D: Deserializer<'de>> Deserializer<'de> for $name<'_, D> {
+            type Error = D::Error;
+
+            forward_deserialize! {
+                $wrap;
+                deserialize_any() deserialize_bool() deserialize_i8() deserialize_i16()
+                deserialize_i32() deserialize_i64() deserialize_i128() deserialize_u8()
+                deserialize_u16() deserialize_u32() deserialize_u64() deserialize_u128()
+                deserialize_f32() deserialize_f64() deserialize_char() deserialize_str()
+                deserialize_string() deserialize_bytes() deserialize_byte_buf()
+                deserialize_option() deserialize_unit() deserialize_seq() deserialize_map()
+                deserialize_identifier() deserialize_ignored_any()
+                deserialize_unit_struct(name: &'static str)
+                deserialize_newtype_struct(name: &'static str)
+                deserialize_tuple(len: usize)
+                deserialize_tuple_struct(name: &'static str, len: usize)
+                deserialize_struct(name: &'static str, fields: &'static [&'static str])
+                deserialize_enum(name: &'static str, variants: &'static [&'static str])
+            }
+
+            fn is_human_readable(&self) -> bool {
+                self.de.is_human_readable()
+            }
+        }
+    };
+}
+
+deserializer_impl!(Tracked, TrackedVisitor);
+deserializer_impl!(CaptureKey, CaptureKeyVisitor);
+
+/// Forwards each visit method to the inner visitor, optionally recording
+/// the value as the key
+macro_rules! forward_visit {
+    ($($method:ident: $ty:ty => $capture:ident),*) => {
+        $(
+            fn $method<E: de::Error>(self, v: $ty) -> Result<Self::Value, E> {
+                forward_visit!(@$capture self, v);
+                self.visitor.$method(v)
+            }
+        )*
+    };
+    (@key $self:ident, $v:ident) => {
+        let _ = write!($self.trail.start_key(), "{}", $v);
+    };
+    (@skip $self:ident, $v:ident) => {};
+}
+
+macro_rules! visitor_impl {
+    ($name:ident, $capture:ident) => {
+        impl<'de, V: Visitor<'de>> Visitor<'de> for $name<'_, V> {
+            type Value = V::Value;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                self.visitor.expecting(formatter)
+            }
+
+            forward_visit! {
+                visit_bool: bool => $capture,
+                visit_i8: i8 => $capture,
+                visit_i16: i16 => $capture,
+                visit_i32: i32 => $capture,
+                visit_i64: i64 => $capture,
+                visit_i128: i128 => $capture,
+                visit_u8: u8 => $capture,
+                visit_u16: u16 => $capture,
+                visit_u32: u32 => $capture,
+                visit_u64: u64 => $capture,
+                visit_u128: u128 => $capture,
+                visit_f32: f32 => $capture,
+                visit_f64: f64 => $capture,
+                visit_char: char => $capture,
+                visit_str: &str => $capture,
+                visit_borrowed_str: &'de str => $capture,
+                visit_bytes: &[u8] => skip,
+                visit_borrowed_bytes: &'de [u8] => skip
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+                forward_visit!(@$capture self, v);
+                self.visitor.visit_string(v)
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                self.visitor.visit_byte_buf(v)
+            }
+
+            fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+                self.visitor.visit_none()
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+                self.visitor.visit_unit()
+            }
+
+            fn visit_some<D: Deserializer<'de>>(self, de: D) -> Result<Self::Value, D::Error> {
+                self.visitor.visit_some(Tracked::new(de, self.trail))
+            }
+
+            fn visit_newtype_struct<D: Deserializer<'de>>(
+                self,
+                de: D,
+            ) -> Result<Self::Value, D::Error> {
+                self.visitor.visit_newtype_struct(Tracked::new(de, self.trail))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                self.visitor.visit_seq(TrackedSeq {
+                    seq,
+                    trail: self.trail,
+                })
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                self.visitor.visit_map(TrackedMap {
+                    map,
+                    trail: self.trail,
+                })
+            }
+
+            fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+                self.visitor.visit_enum(data)
+            }
+        }
+    };
+}
+
+visitor_impl!(TrackedVisitor, skip);
+visitor_impl!(CaptureKeyVisitor, key);
+
+struct TrackedVisitor<'a, V> {
+    visitor: V,
+    trail: &'a KeyTrail,
+}
+
+/// Deserializes a map key, recording it in the trail
+struct CaptureKey<'a, D> {
+    de: D,
+    trail: &'a KeyTrail,
+}
+
+struct CaptureKeyVisitor<'a, V> {
+    visitor: V,
+    trail: &'a KeyTrail,
+}
+
+struct TrackedSeq<'a, A> {
+    seq: A,
+    trail: &'a KeyTrail,
+}
+
+impl<'de, A: SeqAccess<'de>> SeqAccess<'de> for TrackedSeq<'_, A> {
+    type Error = A::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.seq.next_element_seed(TrackedSeed {
+            seed,
+            trail: self.trail,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.seq.size_hint()
+    }
+}
+
+struct TrackedMap<'a, A> {
+    map: A,
+    trail: &'a KeyTrail,
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for TrackedMap<'_, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        self.map.next_key_seed(KeySeed {
+            seed,
+            trail: self.trail,
+        })
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        self.trail.push();
+        let value = self.map.next_value_seed(TrackedSeed {
+            seed,
+            trail: self.trail,
+        })?;
+        self.trail.pop();
+        Ok(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.map.size_hint()
+    }
+}
+
+struct KeySeed<'a, S> {
+    seed: S,
+    trail: &'a KeyTrail,
+}
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for KeySeed<'_, S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, de: D) -> Result<Self::Value, D::Error> {
+        self.trail.in_key.set(true);
+        let key = self.seed.deserialize(CaptureKey {
+            de,
+            trail: self.trail,
+        });
+        self.trail.in_key.set(false);
+        key
+    }
+}
+
+struct TrackedSeed<'a, S> {
+    seed: S,
+    trail: &'a KeyTrail,
+}
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for TrackedSeed<'_, S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, de: D) -> Result<Self::Value, D::Error> {
+        self.seed.deserialize(Tracked::new(de, self.trail))
+    }
+}
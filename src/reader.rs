@@ -0,0 +1,179 @@
+use crate::{
+    binary::{decode_token, payload, BinaryToken, Payload},
+    Hoi4Error, Hoi4ErrorKind,
+};
+use std::io::Read;
+
+/// The longest field: a string key, the equal operator, and a string value,
+/// each string being an id, a length, and up to `u16::MAX` bytes
+pub(crate) const MAX_FIELD_LEN: usize = 2 * (4 + u16::MAX as usize) + 2;
+
+/// Large enough to hold the longest field, so that lookahead never needs to
+/// grow the buffer
+pub(crate) const DEFAULT_CAPACITY: usize = 1 << 18;
+
+const _: () = assert!(MAX_FIELD_LEN <= DEFAULT_CAPACITY);
+
+/// Reads binary save data incrementally through a fixed size buffer
+#[derive(Debug)]
+pub(crate) struct TokenReader<R> {
+    reader: R,
+    buf: Box<[u8]>,
+    start: usize,
+    end: usize,
+    consumed: usize,
+}
+
+impl<R: Read> TokenReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self::with_capacity(reader, DEFAULT_CAPACITY)
+    }
+
+    pub(crate) fn with_capacity(reader: R, capacity: usize) -> Self {
+        TokenReader {
+            reader,
+            buf: vec![0u8; capacity].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            consumed: 0,
+        }
+    }
+
+    /// Ensures that at least `min` bytes are buffered, unless the underlying
+    /// reader is exhausted first
+    fn fill(&mut self, min: usize) -> Result<(), Hoi4Error> {
+        if self.end - self.start >= min {
+            return Ok(());
+        }
+
+        debug_assert!(min <= self.buf.len());
+        self.buf.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+
+        while self.end < min {
+            match self.reader.read(&mut self.buf[self.end..]) {
+                Ok(0) => break,
+                Ok(read) => self.end += read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns up to `len` upcoming bytes without consuming them. The
+    /// returned slice is shorter than requested only at the end of input.
+    pub(crate) fn peek(&mut self, len: usize) -> Result<&[u8], Hoi4Error> {
+        self.fill(len)?;
+        let end = self.end.min(self.start + len);
+        Ok(&self.buf[self.start..end])
+    }
+
+    /// Reads the next token id. Returns `None` when the input is exhausted
+    /// on a token boundary.
+    pub(crate) fn read_id(&mut self) -> Result<Option<u16>, Hoi4Error> {
+        match *self.peek(2)? {
+            [] => Ok(None),
+            [a, b] => {
+                self.consume(2);
+                Ok(Some(u16::from_le_bytes([a, b])))
+            }
+            _ => Err(Hoi4ErrorKind::Eof.into()),
+        }
+    }
+
+    pub(crate) fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Hoi4Error> {
+        let data = self.peek(N)?;
+        let result = data.try_into().map_err(|_| Hoi4ErrorKind::Eof)?;
+        self.consume(N);
+        Ok(result)
+    }
+
+    pub(crate) fn read_bytes(&mut self, len: usize) -> Result<&[u8], Hoi4Error> {
+        self.fill(len)?;
+        if self.end - self.start < len {
+            return Err(Hoi4ErrorKind::Eof.into());
+        }
+
+        let start = self.start;
+        self.consume(len);
+        Ok(&self.buf[start..start + len])
+    }
+
+    /// Reads a length prefixed string
+    pub(crate) fn read_string(&mut self) -> Result<&[u8], Hoi4Error> {
+        let len = u16::from_le_bytes(self.read_array::<2>()?);
+        self.read_bytes(usize::from(len))
+    }
+
+    /// Reads the payload that follows the token id, sans any length prefix
+    pub(crate) fn read_payload(
+        &mut self,
+        id: u16,
+        new_save_format: bool,
+    ) -> Result<&[u8], Hoi4Error> {
+        match payload(id, new_save_format) {
+            Payload::None => Ok(&[]),
+            Payload::Fixed(len) => self.read_bytes(len),
+            Payload::String => self.read_string(),
+        }
+    }
+
+    /// Reads the next token. See [`decode_token`].
+    pub(crate) fn read_token(
+        &mut self,
+        new_save_format: bool,
+    ) -> Result<Option<BinaryToken<'_>>, Hoi4Error> {
+        let Some(id) = self.read_id()? else {
+            return Ok(None);
+        };
+
+        let data = self.read_payload(id, new_save_format)?;
+        Ok(Some(decode_token(id, data)))
+    }
+
+    /// The number of bytes that have been consumed from the input
+    pub(crate) fn position(&self) -> usize {
+        self.consumed
+    }
+
+    pub(crate) fn skip(&mut self, len: usize) -> Result<(), Hoi4Error> {
+        self.read_bytes(len).map(|_| ())
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.start += len;
+        self.consumed += len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_across_refills() {
+        let data = [0x01, 0x00, 0x0f, 0x00, 0x03, 0x00, b'a', b'b', b'c', 0x04];
+        // Short reads at each boundary exercise buffer refills
+        let input = data[..3].chain(&data[3..7]).chain(&data[7..]);
+        let mut reader = TokenReader::with_capacity(input, 8);
+        assert_eq!(reader.read_id().unwrap(), Some(0x0001));
+        assert_eq!(reader.peek(4).unwrap(), &[0x0f, 0x00, 0x03, 0x00]);
+        assert_eq!(reader.read_id().unwrap(), Some(0x000f));
+        assert_eq!(reader.read_string().unwrap(), b"abc");
+        assert!(matches!(
+            reader.read_id().unwrap_err().kind(),
+            Hoi4ErrorKind::Eof
+        ));
+    }
+
+    #[test]
+    fn test_read_id_at_end() {
+        let mut reader = TokenReader::new(&[0x03, 0x00][..]);
+        assert_eq!(reader.read_id().unwrap(), Some(0x0003));
+        assert_eq!(reader.read_id().unwrap(), None);
+        assert!(reader.peek(2).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+use crate::path::KeyTrail;
+use jomini::binary::TokenResolver;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+};
+
+/// The unresolved tokens encountered while deserializing a binary save.
+///
+/// Only tokens that the deserializer had to resolve are reported: an unknown
+/// key is skipped along with its value, so unknown tokens nested within a
+/// skipped value or within a field the model does not declare do not appear.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeserializeReport {
+    unknown_tokens: BTreeMap<u16, BTreeSet<String>>,
+}
+
+impl DeserializeReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if every token the deserializer needed was resolved
+    pub fn is_empty(&self) -> bool {
+        self.unknown_tokens.is_empty()
+    }
+
+    /// The unresolved token ids in ascending order
+    pub fn unknown_tokens(&self) -> impl Iterator<Item = u16> + '_ {
+        self.unknown_tokens.keys().copied()
+    }
+
+    /// Dot separated paths where the token was encountered: the enclosing
+    /// object when it was a key and the field when it was a value. The root
+    /// object is the empty path.
+    pub fn paths(&self, token: u16) -> Option<&BTreeSet<String>> {
+        self.unknown_tokens.get(&token)
+    }
+}
+
+/// Wraps a resolver to hold the path of the field being deserialized and,
+/// when enabled, record the tokens that failed to resolve
+pub(crate) struct ReportingResolver<R> {
+    resolver: R,
+    trail: KeyTrail,
+    report: Option<RefCell<DeserializeReport>>,
+}
+
+impl<R> ReportingResolver<R> {
+    pub(crate) fn new(resolver: R) -> Self {
+        ReportingResolver {
+            resolver,
+            trail: KeyTrail::default(),
+            report: None,
+        }
+    }
+
+    pub(crate) fn inner(&self) -> &R {
+        &self.resolver
+    }
+
+    pub(crate) fn trail(&self) -> &KeyTrail {
+        &self.trail
+    }
+
+    /// Forgets the keys left on the trail by a failed deserialization
+    pub(crate) fn clear_trail(&mut self) {
+        self.trail = KeyTrail::default();
+    }
+
+    /// Starts recording unresolved tokens into an empty report
+    pub(crate) fn start_report(&mut self) {
+        self.report = Some(RefCell::default());
+    }
+
+    pub(crate) fn take_report(&mut self) -> DeserializeReport {
+        self.report
+            .take()
+            .map(RefCell::into_inner)
+            .unwrap_or_default()
+    }
+}
+
+impl<R: TokenResolver> TokenResolver for ReportingResolver<R> {
+    fn resolve(&self, token: u16) -> Option<&str> {
+        let result = self.resolver.resolve(token);
+        if let (None, Some(report)) = (result, &self.report) {
+            report
+                .borrow_mut()
+                .unknown_tokens
+                .entry(token)
+                .or_default()
+                .insert(self.trail.token_path());
+        }
+        result
+    }
+
+    fn lookup(&self, index: u32) -> Option<&str> {
+        self.resolver.lookup(index)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.resolver.is_empty()
+    }
+}
@@ -0,0 +1,766 @@
+use crate::{file::TXT_HEADER, models::Hoi4Save, Hoi4Date, Hoi4Error, Hoi4ErrorKind};
+use jomini::{common::PdsDate, TextWriter, TextWriterBuilder};
+use serde::ser::{self, Impossible, Serialize};
+use std::{fmt, io::Write};
+
+impl Hoi4Save {
+    /// Writes the modeled fields in the plaintext format, including the
+    /// `HOI4txt` header, so that they can be parsed again into a
+    /// [`Hoi4Save`]. See [`to_text_writer`] for how values are written.
+    ///
+    /// Only the fields of [`Hoi4Save`] and the models it contains are
+    /// written, so the output is a small subset of the original save and
+    /// is not a save that the game can load. Use
+    /// [`Hoi4ParsedText::editor`](crate::file::Hoi4ParsedText::editor) to
+    /// modify a save while keeping the rest of it intact.
+    pub fn write_text<W: Write>(&self, mut writer: W) -> Result<(), Hoi4Error> {
+        writer.write_all(TXT_HEADER)?;
+        writer.write_all(b"\n")?;
+        to_text_writer(writer, self)
+    }
+}
+
+/// Serializes a struct or map as the body of a plaintext save, without the
+/// `HOI4txt` header
+///
+/// - Fields are written as `key=value` and nested structs and maps as
+///   objects
+/// - `None` and unit values are omitted along with their key
+/// - Strings are quoted
+/// - Dates of fields serialized with [`serialize_date`] or
+///   [`serialize_date_opt`] are written in the game's format (eg:
+///   `1936.1.1.12`). Other dates are ISO 8601 strings.
+/// - A sequence of pairs (eg: `Vec<(CountryTag, Country)>`) is an object
+///   where the first of each pair is the key
+/// - A sequence of structs or maps repeats its key for each element, how
+///   the game stores lists of objects
+/// - Other sequences are arrays. Empty sequences are omitted.
+///
+/// ```rust
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Flags {
+///     name: &'static str,
+///     enabled: bool,
+///     counts: Vec<i32>,
+/// }
+///
+/// let mut out = Vec::new();
+/// let flags = Flags { name: "test", enabled: true, counts: vec![1, 2] };
+/// hoi4save::to_text_writer(&mut out, &flags)?;
+/// let text = String::from_utf8(out).unwrap();
+/// assert_eq!(text, "name=\"test\"\nenabled=yes\ncounts={\n\t1 2\n}\n");
+/// # Ok::<(), hoi4save::Hoi4Error>(())
+/// ```
+pub fn to_text_writer<W, T>(writer: W, value: &T) -> Result<(), Hoi4Error>
+where
+    W: Write,
+    T: Serialize + ?Sized,
+{
+    let mut wtr = TextWriterBuilder::new()
+        .indent_char(b'\t')
+        .indent_factor(1)
+        .from_writer(writer);
+    value.serialize(TextSerializer {
+        wtr: &mut wtr,
+        slot: Slot::Root,
+    })?;
+    wtr.inner().write_all(b"\n")?;
+    Ok(())
+}
+
+impl ser::Error for Hoi4Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        serialize_error(msg)
+    }
+}
+
+fn serialize_error<T: fmt::Display>(msg: T) -> Hoi4Error {
+    Hoi4Error::new(Hoi4ErrorKind::Serialize {
+        msg: msg.to_string(),
+    })
+}
+
+/// The newtype name that marks a date for the text serializer
+const DATE_NAME: &str = "$hoi4save::Date";
+
+/// Serializes a date so that [`to_text_writer`] writes it in the game's
+/// format (eg: `1936.1.1.12`) rather than as a string. Other serializers
+/// see the date unchanged.
+///
+/// ```rust
+/// use hoi4save::Hoi4Date;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct War {
+///     #[serde(serialize_with = "hoi4save::serialize_date")]
+///     start: Hoi4Date,
+/// }
+///
+/// let mut out = Vec::new();
+/// let start = Hoi4Date::from_ymdh(1939, 9, 1, 12);
+/// hoi4save::to_text_writer(&mut out, &War { start })?;
+/// assert_eq!(out, b"start=1939.9.1.12\n");
+/// # Ok::<(), hoi4save::Hoi4Error>(())
+/// ```
+pub fn serialize_date<S>(date: &Hoi4Date, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+{
+    serializer.serialize_newtype_struct(DATE_NAME, date)
+}
+
+/// [`serialize_date`] for an optional date
+pub fn serialize_date_opt<S>(date: &Option<Hoi4Date>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: ser::Serializer,
+{
+    struct GameDate<'a>(&'a Hoi4Date);
+
+    impl Serialize for GameDate<'_> {
+        fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_date(self.0, serializer)
+        }
+    }
+
+    match date {
+        Some(date) => serializer.serialize_some(&GameDate(date)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Reads back a date marked by [`serialize_date`]
+fn marked_date<T: Serialize + ?Sized>(value: &T) -> Result<Hoi4Date, Hoi4Error> {
+    let iso = value.serialize(KeySerializer)?;
+    iso_date(&iso).ok_or_else(|| serialize_error(format!("invalid date: {}", iso)))
+}
+
+/// Parses the ISO 8601 format that dates serialize as (eg: `1936-01-01T11`)
+fn iso_date(s: &str) -> Option<Hoi4Date> {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+    let (date, hour) = s.split_once('T')?;
+    let mut parts = date.splitn(3, '-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if ![year, month, day, hour].into_iter().all(digits) || hour.len() != 2 {
+        return None;
+    }
+
+    let hour = hour.parse::<u8>().ok()?.checked_add(1)?;
+    Hoi4Date::from_ymdh_opt(
+        year.parse().ok()?,
+        month.parse().ok()?,
+        day.parse().ok()?,
+        hour,
+    )
+}
+
+/// Where a value is being written
+enum Slot<'a> {
+    /// The document, which must be a struct or map
+    Root,
+
+    /// The value of a key that has yet to be written
+    Field(&'a str),
+
+    /// An element of a sequence that is written under the key, if any
+    Element(&'a mut SeqMode, Option<&'a str>),
+}
+
+/// How a sequence is written, decided by its first element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SeqMode {
+    Empty,
+    Array,
+    Pairs,
+    Duplicated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Scalar,
+    Object,
+    Array,
+    Pair,
+}
+
+/// Serializes a value in the plaintext format
+struct TextSerializer<'a, W> {
+    wtr: &'a mut TextWriter<W>,
+    slot: Slot<'a>,
+}
+
+impl<'a, W: Write> TextSerializer<'a, W> {
+    /// Writes what precedes a value of the given kind: its key, or the
+    /// start of the sequence that it is the first element of
+    fn begin(&mut self, kind: Kind) -> Result<(), Hoi4Error> {
+        match &mut self.slot {
+            Slot::Root if kind == Kind::Object => {}
+            Slot::Root => {
+                return Err(serialize_error(
+                    "only structs and maps can be written as a document",
+                ))
+            }
+            Slot::Field(key) => self.wtr.write_unquoted(key.as_bytes())?,
+            Slot::Element(mode, key) => {
+                if **mode == SeqMode::Empty {
+                    **mode = match (kind, &key) {
+                        (Kind::Object, Some(_)) => SeqMode::Duplicated,
+                        (Kind::Pair, _) => SeqMode::Pairs,
+                        _ => SeqMode::Array,
+                    };
+
+                    if **mode != SeqMode::Duplicated {
+                        if let Some(key) = key {
+                            self.wtr.write_unquoted(key.as_bytes())?;
+                        }
+
+                        if **mode == SeqMode::Pairs {
+                            self.wtr.write_object_start()?;
+                        } else {
+                            self.wtr.write_array_start()?;
+                        }
+                    }
+                }
+
+                if let (SeqMode::Duplicated, Some(key)) = (**mode, key) {
+                    self.wtr.write_unquoted(key.as_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn scalar<F>(mut self, write: F) -> Result<(), Hoi4Error>
+    where
+        F: FnOnce(&mut TextWriter<W>) -> Result<(), jomini::Error>,
+    {
+        self.begin(Kind::Scalar)?;
+        write(self.wtr)?;
+        Ok(())
+    }
+
+    fn object(mut self) -> Result<ObjectSerializer<'a, W>, Hoi4Error> {
+        self.begin(Kind::Object)?;
+        let close = !matches!(self.slot, Slot::Root);
+        if close {
+            self.wtr.write_object_start()?;
+        }
+
+        Ok(ObjectSerializer {
+            wtr: self.wtr,
+            close,
+            key: None,
+        })
+    }
+
+    fn sequence(mut self) -> Result<SeqSerializer<'a, W>, Hoi4Error> {
+        match self.slot {
+            Slot::Field(key) => Ok(SeqSerializer {
+                wtr: self.wtr,
+                key: Some(key),
+                mode: SeqMode::Empty,
+            }),
+            _ => {
+                self.begin(Kind::Array)?;
+                self.wtr.write_array_start()?;
+                Ok(SeqSerializer {
+                    wtr: self.wtr,
+                    key: None,
+                    mode: SeqMode::Array,
+                })
+            }
+        }
+    }
+}
+
+impl<'a, W: Write> ser::Serializer for TextSerializer<'a, W> {
+    type Ok = ();
+    type Error = Hoi4Error;
+    type SerializeSeq = SeqSerializer<'a, W>;
+    type SerializeTuple = TupleSerializer<'a, W>;
+    type SerializeTupleStruct = SeqSerializer<'a, W>;
+    type SerializeTupleVariant = Impossible<(), Hoi4Error>;
+    type SerializeMap = ObjectSerializer<'a, W>;
+    type SerializeStruct = ObjectSerializer<'a, W>;
+    type SerializeStructVariant = Impossible<(), Hoi4Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Hoi4Error> {
+        self.scalar(|wtr| wtr.write_bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Hoi4Error> {
+        self.serialize_i32(i32::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Hoi4Error> {
+        self.serialize_i32(i32::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Hoi4Error> {
+        self.scalar(|wtr| wtr.write_i32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Hoi4Error> {
+        self.scalar(|wtr| wtr.write_i64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Hoi4Error> {
+        self.serialize_u32(u32::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Hoi4Error> {
+        self.serialize_u32(u32::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Hoi4Error> {
+        self.scalar(|wtr| wtr.write_u32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Hoi4Error> {
+        self.scalar(|wtr| wtr.write_u64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Hoi4Error> {
+        self.scalar(|wtr| wtr.write_f32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Hoi4Error> {
+        self.scalar(|wtr| wtr.write_f64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Hoi4Error> {
+        self.serialize_str(v.encode_utf8(&mut [0u8; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Hoi4Error> {
+        self.scalar(|wtr| wtr.write_quoted(v.as_bytes()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Hoi4Error> {
+        self.scalar(|wtr| wtr.write_quoted(v))
+    }
+
+    fn serialize_none(self) -> Result<(), Hoi4Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Hoi4Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Hoi4Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Hoi4Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Hoi4Error> {
+        self.scalar(|wtr| wtr.write_unquoted(variant.as_bytes()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), Hoi4Error> {
+        if name == DATE_NAME {
+            let date = marked_date(value)?;
+            return self.scalar(|wtr| wtr.write_date(date.game_fmt()));
+        }
+
+        value.serialize(self)
+    }
+
+    /// Written as an object with the variant as the only key
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Hoi4Error> {
+        let mut object = self.object()?;
+        ser::SerializeStruct::serialize_field(&mut object, variant, value)?;
+        ser::SerializeStruct::end(object)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<SeqSerializer<'a, W>, Hoi4Error> {
+        self.sequence()
+    }
+
+    fn serialize_tuple(mut self, len: usize) -> Result<TupleSerializer<'a, W>, Hoi4Error> {
+        let pair = len == 2
+            && matches!(&self.slot, Slot::Element(mode, _) if matches!(**mode, SeqMode::Empty | SeqMode::Pairs));
+        if pair {
+            self.begin(Kind::Pair)?;
+            Ok(TupleSerializer::Pair {
+                wtr: self.wtr,
+                key: None,
+            })
+        } else {
+            self.sequence().map(TupleSerializer::Seq)
+        }
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<SeqSerializer<'a, W>, Hoi4Error> {
+        self.sequence()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Hoi4Error> {
+        Err(serialize_error(format!(
+            "unable to write tuple variant {}::{}",
+            name, variant
+        )))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<ObjectSerializer<'a, W>, Hoi4Error> {
+        self.object()
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<ObjectSerializer<'a, W>, Hoi4Error> {
+        self.object()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Hoi4Error> {
+        Err(serialize_error(format!(
+            "unable to write struct variant {}::{}",
+            name, variant
+        )))
+    }
+}
+
+/// Writes the fields of a struct or the entries of a map
+struct ObjectSerializer<'a, W> {
+    wtr: &'a mut TextWriter<W>,
+    close: bool,
+    key: Option<String>,
+}
+
+impl<W: Write> ser::SerializeStruct for ObjectSerializer<'_, W> {
+    type Ok = ();
+    type Error = Hoi4Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Hoi4Error> {
+        value.serialize(TextSerializer {
+            wtr: &mut *self.wtr,
+            slot: Slot::Field(key),
+        })
+    }
+
+    fn end(self) -> Result<(), Hoi4Error> {
+        if self.close {
+            self.wtr.write_end()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> ser::SerializeMap for ObjectSerializer<'_, W> {
+    type Ok = ();
+    type Error = Hoi4Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Hoi4Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Hoi4Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| serialize_error("map value serialized before its key"))?;
+        value.serialize(TextSerializer {
+            wtr: &mut *self.wtr,
+            slot: Slot::Field(&key),
+        })
+    }
+
+    fn end(self) -> Result<(), Hoi4Error> {
+        ser::SerializeStruct::end(self)
+    }
+}
+
+/// Writes the elements of a sequence under the key, if any
+struct SeqSerializer<'a, W> {
+    wtr: &'a mut TextWriter<W>,
+    key: Option<&'a str>,
+    mode: SeqMode,
+}
+
+impl<W: Write> ser::SerializeSeq for SeqSerializer<'_, W> {
+    type Ok = ();
+    type Error = Hoi4Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Hoi4Error> {
+        value.serialize(TextSerializer {
+            wtr: &mut *self.wtr,
+            slot: Slot::Element(&mut self.mode, self.key),
+        })
+    }
+
+    fn end(self) -> Result<(), Hoi4Error> {
+        if matches!(self.mode, SeqMode::Array | SeqMode::Pairs) {
+            self.wtr.write_end()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> ser::SerializeTupleStruct for SeqSerializer<'_, W> {
+    type Ok = ();
+    type Error = Hoi4Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Hoi4Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Hoi4Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// Writes a pair in a sequence of pairs as `key=value`, or any other tuple
+/// as a sequence
+enum TupleSerializer<'a, W> {
+    Pair {
+        wtr: &'a mut TextWriter<W>,
+        key: Option<String>,
+    },
+    Seq(SeqSerializer<'a, W>),
+}
+
+impl<W: Write> ser::SerializeTuple for TupleSerializer<'_, W> {
+    type Ok = ();
+    type Error = Hoi4Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Hoi4Error> {
+        match self {
+            TupleSerializer::Pair {
+                key: key @ None, ..
+            } => {
+                *key = Some(value.serialize(KeySerializer)?);
+                Ok(())
+            }
+            TupleSerializer::Pair {
+                wtr,
+                key: Some(key),
+            } => value.serialize(TextSerializer {
+                wtr: &mut **wtr,
+                slot: Slot::Field(key.as_str()),
+            }),
+            TupleSerializer::Seq(seq) => ser::SerializeSeq::serialize_element(seq, value),
+        }
+    }
+
+    fn end(self) -> Result<(), Hoi4Error> {
+        match self {
+            TupleSerializer::Pair { .. } => Ok(()),
+            TupleSerializer::Seq(seq) => ser::SerializeSeq::end(seq),
+        }
+    }
+}
+
+/// Serializes a map key or the first of a pair, which must be a scalar
+struct KeySerializer;
+
+impl KeySerializer {
+    fn unsupported(kind: &str) -> Hoi4Error {
+        serialize_error(format!("a {} can not be written as a key", kind))
+    }
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Hoi4Error;
+    type SerializeSeq = Impossible<String, Hoi4Error>;
+    type SerializeTuple = Impossible<String, Hoi4Error>;
+    type SerializeTupleStruct = Impossible<String, Hoi4Error>;
+    type SerializeTupleVariant = Impossible<String, Hoi4Error>;
+    type SerializeMap = Impossible<String, Hoi4Error>;
+    type SerializeStruct = Impossible<String, Hoi4Error>;
+    type SerializeStructVariant = Impossible<String, Hoi4Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<String, Hoi4Error> {
+        Ok(String::from(if v { "yes" } else { "no" }))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, Hoi4Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, Hoi4Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, Hoi4Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, Hoi4Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, Hoi4Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, Hoi4Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, Hoi4Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, Hoi4Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<String, Hoi4Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<String, Hoi4Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, Hoi4Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, Hoi4Error> {
+        Ok(String::from(v))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Hoi4Error> {
+        Err(Self::unsupported("byte array"))
+    }
+
+    fn serialize_none(self) -> Result<String, Hoi4Error> {
+        Err(Self::unsupported("none"))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, Hoi4Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, Hoi4Error> {
+        Err(Self::unsupported("unit"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Hoi4Error> {
+        Err(Self::unsupported("unit struct"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Hoi4Error> {
+        Ok(String::from(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<String, Hoi4Error> {
+        if name == DATE_NAME {
+            return Ok(marked_date(value)?.game_fmt().to_string());
+        }
+
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Hoi4Error> {
+        Err(Self::unsupported("newtype variant"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Hoi4Error> {
+        Err(Self::unsupported("sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Hoi4Error> {
+        Err(Self::unsupported("tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Hoi4Error> {
+        Err(Self::unsupported("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Hoi4Error> {
+        Err(Self::unsupported("tuple variant"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Hoi4Error> {
+        Err(Self::unsupported("map"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Hoi4Error> {
+        Err(Self::unsupported("struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Hoi4Error> {
+        Err(Self::unsupported("struct variant"))
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{binary::BinaryToken, reader::TokenReader, Hoi4Date, Hoi4Error};
+use jomini::{binary::TokenResolver, text, Scalar};
+use serde::Serialize;
+use std::io::Read;
+
+/// Metadata found at the start of a save
+///
+/// Only the scalar fields that precede the first object in the save are
+/// examined, so a summary is cheap to compute even for large saves.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SaveSummary {
+    /// Tag of the player's country
+    pub player: Option<String>,
+
+    /// The in-game date of the save
+    pub date: Option<Hoi4Date>,
+
+    /// Version of the save format
+    pub save_version: Option<i32>,
+
+    /// Version of the game that wrote the save
+    pub version: Option<String>,
+}
+
+impl SaveSummary {
+    fn is_complete(&self) -> bool {
+        self.player.is_some()
+            && self.date.is_some()
+            && self.save_version.is_some()
+            && self.version.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Player,
+    Date,
+    SaveVersion,
+    Version,
+    Other,
+}
+
+impl Field {
+    fn from_name(name: &[u8]) -> Field {
+        match name {
+            b"player" => Field::Player,
+            b"date" => Field::Date,
+            b"save_version" => Field::SaveVersion,
+            b"version" => Field::Version,
+            _ => Field::Other,
+        }
+    }
+}
+
+/// Where the scan is within a key value pair
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Key,
+    Operator(Field),
+    Value(Field),
+}
+
+pub(crate) fn text_summary<R: Read>(input: R) -> Result<SaveSummary, Hoi4Error> {
+    let mut reader = text::TokenReader::new(input);
+    let mut summary = SaveSummary::default();
+    let mut state = State::Key;
+
+    while let Some(token) = reader.next().map_err(jomini::Error::from)? {
+        state = match (state, token) {
+            (_, text::Token::Open) => break,
+            (State::Operator(field), text::Token::Operator(_)) => State::Value(field),
+            (State::Value(field), text::Token::Quoted(x) | text::Token::Unquoted(x)) => {
+                set_text_field(&mut summary, field, x);
+                if summary.is_complete() {
+                    break;
+                }
+                State::Key
+            }
+            (_, text::Token::Quoted(x) | text::Token::Unquoted(x)) => {
+                State::Operator(Field::from_name(x.as_bytes()))
+            }
+            _ => State::Key,
+        };
+    }
+
+    Ok(summary)
+}
+
+fn set_text_field(summary: &mut SaveSummary, field: Field, value: Scalar) {
+    match field {
+        Field::Player => summary.player = Some(value.to_string()),
+        Field::Date => summary.date = Hoi4Date::parse(value.as_bytes()).ok(),
+        Field::SaveVersion => {
+            summary.save_version = value.to_i64().ok().and_then(|x| i32::try_from(x).ok())
+        }
+        Field::Version => summary.version = Some(value.to_string()),
+        Field::Other => {}
+    }
+}
+
+pub(crate) fn binary_summary<R, Resolver>(
+    input: R,
+    resolver: Resolver,
+) -> Result<SaveSummary, Hoi4Error>
+where
+    R: Read,
+    Resolver: TokenResolver,
+{
+    let mut reader = TokenReader::new(input);
+    let mut summary = SaveSummary::default();
+    let mut state = State::Key;
+    let mut new_save_format = false;
+
+    while let Some(token) = reader.read_token(new_save_format)? {
+        state = match (state, token) {
+            (_, BinaryToken::Open(_)) => break,
+            (State::Operator(field), BinaryToken::Equal) => State::Value(field),
+            (State::Value(field), token) => {
+                match (field, token) {
+                    (Field::Player, BinaryToken::Quoted(x) | BinaryToken::Unquoted(x)) => {
+                        summary.player = Some(String::from_utf8_lossy(x).into_owned())
+                    }
+                    (Field::Version, BinaryToken::Quoted(x) | BinaryToken::Unquoted(x)) => {
+                        summary.version = Some(String::from_utf8_lossy(x).into_owned())
+                    }
+                    (Field::Date, BinaryToken::I32(x)) => summary.date = Hoi4Date::from_binary(x),
+                    (Field::SaveVersion, BinaryToken::I32(x)) => {
+                        new_save_format = x >= 30;
+                        summary.save_version = Some(x)
+                    }
+                    _ => {}
+                }
+
+                if summary.is_complete() {
+                    break;
+                }
+                State::Key
+            }
+            (_, BinaryToken::Id(id)) => {
+                let name = resolver.resolve(id).unwrap_or_default();
+                State::Operator(Field::from_name(name.as_bytes()))
+            }
+            (_, BinaryToken::Quoted(x) | BinaryToken::Unquoted(x)) => {
+                State::Operator(Field::from_name(x))
+            }
+            _ => State::Key,
+        };
+    }
+
+    Ok(summary)
+}
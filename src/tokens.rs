@@ -0,0 +1,486 @@
+use crate::{Hoi4Error, Hoi4ErrorKind, SaveSummary};
+use jomini::binary::TokenResolver;
+use std::{
+    collections::HashMap,
+    env,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+/// Identifies a token table in the compact binary format
+const BINARY_MAGIC: &[u8] = b"HOI4tok1";
+
+/// A mapping of binary token ids to field names that can be merged with,
+/// and compared against, other tables
+///
+/// ```rust
+/// use hoi4save::TokenTable;
+/// let mut table = TokenTable::from_text_lines(&b"0x2000 player\n0x2001 date\n"[..])?;
+/// let patch = TokenTable::from_text_lines(&b"0x2001 date\n0x2002 save_version\n"[..])?;
+/// let conflicts = table.merge(&patch);
+/// assert!(conflicts.is_empty());
+/// assert_eq!(table.len(), 3);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenTable {
+    tokens: HashMap<u16, String>,
+}
+
+/// A token id that is assigned different names by two tables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenConflict {
+    pub id: u16,
+
+    /// The name in the table being merged into or compared from
+    pub ours: String,
+
+    /// The name in the other table
+    pub theirs: String,
+}
+
+/// The differences between two token tables, each ordered by token id
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenDiff {
+    /// Tokens only in the other table
+    pub added: Vec<(u16, String)>,
+
+    /// Tokens only in this table
+    pub removed: Vec<(u16, String)>,
+
+    /// Tokens that are named differently
+    pub changed: Vec<TokenConflict>,
+}
+
+impl TokenDiff {
+    /// Returns true if the tables are identical
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl TokenTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a table of lines where each line is a hex token id and a name
+    /// separated by a space (eg: `0x2000 player`), the same format as
+    /// [`BasicTokenResolver::from_text_lines`](jomini::binary::BasicTokenResolver::from_text_lines).
+    /// Blank lines are ignored.
+    pub fn from_text_lines<R: BufRead>(reader: R) -> Result<Self, Hoi4Error> {
+        let mut table = TokenTable::new();
+        let mut offset = 0;
+        for (idx, line) in reader.split(b'\n').enumerate() {
+            let line = line?;
+            let start = offset;
+            offset += line.len() + 1;
+            let line = std::str::from_utf8(&line).map_err(|e| Hoi4ErrorKind::InvalidUtf8 {
+                offset: start + e.valid_up_to(),
+            })?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let invalid = || Hoi4ErrorKind::InvalidTokenLine { line: idx + 1 };
+            let (id, name) = line.split_once(' ').ok_or_else(invalid)?;
+            let id = u16::from_str_radix(id.trim_start_matches("0x"), 16).map_err(|_| invalid())?;
+            table.insert(id, name.trim_ascii_end());
+        }
+
+        Ok(table)
+    }
+
+    /// Reads a table in the compact binary format written by
+    /// [`TokenTable::write_binary`]
+    pub fn from_binary(data: &[u8]) -> Result<Self, Hoi4Error> {
+        let invalid = |msg: &str| Hoi4ErrorKind::InvalidTokenTable {
+            msg: msg.to_string(),
+        };
+
+        let full = data;
+        let mut data = data
+            .strip_prefix(BINARY_MAGIC)
+            .ok_or_else(|| invalid("missing binary header"))?;
+        let mut table = TokenTable::new();
+        while let Some((&[a, b, len], rest)) = data.split_first_chunk::<3>() {
+            let (name, rest) = rest
+                .split_at_checked(usize::from(len))
+                .ok_or_else(|| invalid("truncated name"))?;
+            let name = std::str::from_utf8(name).map_err(|e| {
+                let start = full.len() - data.len() + 3;
+                Hoi4ErrorKind::InvalidUtf8 {
+                    offset: start + e.valid_up_to(),
+                }
+            })?;
+            table.insert(u16::from_le_bytes([a, b]), name);
+            data = rest;
+        }
+
+        if !data.is_empty() {
+            return Err(invalid("truncated token").into());
+        }
+
+        Ok(table)
+    }
+
+    /// Writes the table in a compact binary format: a header followed by
+    /// each token id (little endian), the length of its name as a byte, and
+    /// the name.
+    pub fn write_binary<W: Write>(&self, mut writer: W) -> Result<(), Hoi4Error> {
+        writer.write_all(BINARY_MAGIC)?;
+        for (id, name) in self.sorted() {
+            let len = u8::try_from(name.len()).map_err(|_| Hoi4ErrorKind::InvalidTokenTable {
+                msg: format!("name of token 0x{:04x} is longer than 255 bytes", id),
+            })?;
+            writer.write_all(&id.to_le_bytes())?;
+            writer.write_all(&[len])?;
+            writer.write_all(name.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a table in either the text lines or the compact binary format
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Hoi4Error> {
+        if data.starts_with(BINARY_MAGIC) {
+            Self::from_binary(data)
+        } else {
+            Self::from_text_lines(data)
+        }
+    }
+
+    /// Writes the table in the format read by [`TokenTable::from_text_lines`],
+    /// ordered by token id
+    pub fn write_text_lines<W: Write>(&self, mut writer: W) -> Result<(), Hoi4Error> {
+        for (id, name) in self.sorted() {
+            writeln!(writer, "0x{:04x} {}", id, name)?;
+        }
+        Ok(())
+    }
+
+    /// Names the token, returning the previous name if there was one
+    pub fn insert(&mut self, id: u16, name: impl Into<String>) -> Option<String> {
+        self.tokens.insert(id, name.into())
+    }
+
+    pub fn get(&self, id: u16) -> Option<&str> {
+        self.tokens.get(&id).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Iterates over the tokens in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &str)> + '_ {
+        self.tokens.iter().map(|(id, name)| (*id, name.as_str()))
+    }
+
+    fn sorted(&self) -> Vec<(u16, &str)> {
+        let mut tokens: Vec<_> = self.iter().collect();
+        tokens.sort_unstable_by_key(|(id, _)| *id);
+        tokens
+    }
+
+    /// Adds the tokens of the other table that are missing from this one.
+    /// Tokens that the tables name differently keep the name from this table
+    /// and are returned as conflicts.
+    pub fn merge(&mut self, other: &TokenTable) -> Vec<TokenConflict> {
+        let conflicts = self.conflicts(other);
+        for (id, name) in other.iter() {
+            self.tokens.entry(id).or_insert_with(|| name.to_string());
+        }
+        conflicts
+    }
+
+    /// Returns the tokens that the tables name differently, ordered by id
+    pub fn conflicts(&self, other: &TokenTable) -> Vec<TokenConflict> {
+        self.diff(other).changed
+    }
+
+    /// Compares this table to the other table
+    pub fn diff(&self, other: &TokenTable) -> TokenDiff {
+        let mut diff = TokenDiff::default();
+        for (id, ours) in self.sorted() {
+            match other.get(id) {
+                None => diff.removed.push((id, ours.to_string())),
+                Some(theirs) if theirs != ours => diff.changed.push(TokenConflict {
+                    id,
+                    ours: ours.to_string(),
+                    theirs: theirs.to_string(),
+                }),
+                Some(_) => {}
+            }
+        }
+
+        for (id, theirs) in other.sorted() {
+            if self.get(id).is_none() {
+                diff.added.push((id, theirs.to_string()));
+            }
+        }
+
+        diff
+    }
+}
+
+impl TokenResolver for TokenTable {
+    fn resolve(&self, token: u16) -> Option<&str> {
+        self.get(token)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+}
+
+impl FromIterator<(u16, String)> for TokenTable {
+    fn from_iter<T: IntoIterator<Item = (u16, String)>>(iter: T) -> Self {
+        TokenTable {
+            tokens: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct VersionedTable {
+    save_version: i32,
+    game_version: Option<Vec<u32>>,
+    table: TokenTable,
+}
+
+/// Token tables for several game patches, where the table for a save is the
+/// one registered for the newest version that the save is not older than
+///
+/// ```rust
+/// use hoi4save::{TokenTable, TokenTables};
+/// let mut tables = TokenTables::new();
+/// tables.insert(22, Some("1.12"), TokenTable::from_text_lines(&b"0x2000 player\n"[..])?);
+/// tables.insert(30, Some("1.14"), TokenTable::from_text_lines(&b"0x2000 player_tag\n"[..])?);
+/// assert_eq!(tables.for_save_version(25).and_then(|x| x.get(0x2000)), Some("player"));
+/// assert_eq!(tables.for_game_version("Collie v1.14.2").and_then(|x| x.get(0x2000)), Some("player_tag"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenTables {
+    tables: Vec<VersionedTable>,
+}
+
+impl TokenTables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the table for saves from the given save version onwards,
+    /// and optionally the game version that introduced it (eg: `1.14`)
+    pub fn insert(&mut self, save_version: i32, game_version: Option<&str>, table: TokenTable) {
+        self.tables.push(VersionedTable {
+            save_version,
+            game_version: game_version.and_then(parse_game_version),
+            table,
+        });
+    }
+
+    /// The table for a save of the given save version
+    pub fn for_save_version(&self, save_version: i32) -> Option<&TokenTable> {
+        self.tables
+            .iter()
+            .filter(|x| x.save_version <= save_version)
+            .max_by_key(|x| x.save_version)
+            .map(|x| &x.table)
+    }
+
+    /// The table for a save written by the given game version, as it
+    /// appears in a save (eg: `Collie v1.10.8`)
+    pub fn for_game_version(&self, version: &str) -> Option<&TokenTable> {
+        let version = parse_game_version(version)?;
+        self.tables
+            .iter()
+            .filter_map(|x| x.game_version.as_ref().map(|v| (v, &x.table)))
+            .filter(|(v, _)| **v <= version)
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, table)| table)
+    }
+
+    /// The table for the save with the given summary, selected by save
+    /// version and otherwise by game version
+    pub fn for_summary(&self, summary: &SaveSummary) -> Option<&TokenTable> {
+        summary
+            .save_version
+            .and_then(|x| self.for_save_version(x))
+            .or_else(|| {
+                summary
+                    .version
+                    .as_deref()
+                    .and_then(|x| self.for_game_version(x))
+            })
+    }
+
+    /// Merges every table into one, where newer tables take precedence
+    pub fn merged(&self) -> TokenTable {
+        let mut tables: Vec<_> = self.tables.iter().collect();
+        tables.sort_by_key(|x| std::cmp::Reverse(x.save_version));
+        let mut result = TokenTable::new();
+        for table in tables {
+            result.merge(&table.table);
+        }
+        result
+    }
+}
+
+/// Extracts the numeric components of a game version (eg: `1.10.8` from
+/// `Collie v1.10.8`)
+fn parse_game_version(version: &str) -> Option<Vec<u32>> {
+    let start = version.find(|c: char| c.is_ascii_digit())?;
+    let numbers = version[start..]
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .next()?;
+    numbers
+        .split('.')
+        .filter(|x| !x.is_empty())
+        .map(|x| x.parse().ok())
+        .collect()
+}
+
+/// Where a [`TokenLoader`] found its token table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenSource {
+    /// The file named by the `HOI4_IRONMAN_TOKENS` environment variable
+    EnvVar(PathBuf),
+
+    /// The path given to [`TokenLoader::path`]
+    Path(PathBuf),
+
+    /// The `hoi4save` directory of the user's config directory
+    ConfigDir(PathBuf),
+
+    /// No table was found, so an empty table was loaded
+    NotFound,
+}
+
+/// Finds and loads the token table for binary saves, so that tools built on
+/// this crate resolve tokens the same way
+///
+/// The first of these to exist is loaded:
+///
+/// 1. The file named by the `HOI4_IRONMAN_TOKENS` environment variable. An
+///    empty variable is the same as an unset variable, and a file that can't
+///    be read is an error.
+/// 2. The path given to [`TokenLoader::path`]
+/// 3. `hoi4save/hoi4.txt` or `hoi4save/hoi4.bin` in the config directory:
+///    `%APPDATA%` on Windows, otherwise `$XDG_CONFIG_HOME`, or
+///    `$HOME/.config` when that is unset
+///
+/// The environment is read by [`TokenLoader::new`], and either input can be
+/// overridden with [`TokenLoader::env_path`] and [`TokenLoader::config_dir`].
+/// Either file format of [`TokenTable::from_bytes`] may be used.
+///
+/// ```rust,no_run
+/// use hoi4save::TokenLoader;
+/// let (tokens, source) = TokenLoader::new().path("assets/hoi4.txt").load()?;
+/// eprintln!("loaded {} tokens from {:?}", tokens.len(), source);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct TokenLoader {
+    path: Option<PathBuf>,
+    env_path: Option<PathBuf>,
+    config_dir: Option<PathBuf>,
+}
+
+impl Default for TokenLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TokenLoader {
+    /// The environment variable that names the token file
+    pub const ENV_VAR: &'static str = "HOI4_IRONMAN_TOKENS";
+
+    pub fn new() -> Self {
+        let non_empty = |name: &str| env::var_os(name).filter(|x| !x.is_empty());
+        let config_dir = if cfg!(windows) {
+            non_empty("APPDATA").map(PathBuf::from)
+        } else {
+            non_empty("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| non_empty("HOME").map(|home| Path::new(&home).join(".config")))
+        };
+
+        TokenLoader {
+            path: None,
+            env_path: non_empty(Self::ENV_VAR).map(PathBuf::from),
+            config_dir,
+        }
+    }
+
+    /// Sets the path to check when the environment variable is not set
+    pub fn path(self, path: impl Into<PathBuf>) -> Self {
+        TokenLoader {
+            path: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Overrides the path read from the [`Self::ENV_VAR`] environment
+    /// variable, where `None` is the same as the variable being unset
+    pub fn env_path(self, path: Option<PathBuf>) -> Self {
+        TokenLoader {
+            env_path: path,
+            ..self
+        }
+    }
+
+    /// Overrides the config directory read from `%APPDATA%`,
+    /// `$XDG_CONFIG_HOME` or `$HOME`, where `None` skips checking the config
+    /// directory
+    pub fn config_dir(self, dir: Option<PathBuf>) -> Self {
+        TokenLoader {
+            config_dir: dir,
+            ..self
+        }
+    }
+
+    fn config_paths(&self) -> Vec<PathBuf> {
+        match &self.config_dir {
+            Some(config) => vec![
+                config.join("hoi4save").join("hoi4.txt"),
+                config.join("hoi4save").join("hoi4.bin"),
+            ],
+            None => Vec::new(),
+        }
+    }
+
+    /// Loads the first token table found, and where it was found
+    pub fn load(&self) -> Result<(TokenTable, TokenSource), Hoi4Error> {
+        if let Some(path) = &self.env_path {
+            let table = TokenTable::from_bytes(&std::fs::read(path)?)?;
+            return Ok((table, TokenSource::EnvVar(path.clone())));
+        }
+
+        let candidates = self
+            .path
+            .iter()
+            .cloned()
+            .map(TokenSource::Path)
+            .chain(self.config_paths().into_iter().map(TokenSource::ConfigDir));
+
+        for source in candidates {
+            let (TokenSource::Path(path) | TokenSource::ConfigDir(path)) = &source else {
+                continue;
+            };
+
+            match std::fs::read(path) {
+                Ok(data) => return Ok((TokenTable::from_bytes(&data)?, source)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok((TokenTable::new(), TokenSource::NotFound))
+    }
+}
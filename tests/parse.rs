@@ -1,10 +1,14 @@
 use hoi4save::{
-    file::Hoi4SliceFileKind, models::Hoi4Save, BasicTokenResolver, Encoding, Hoi4Date, Hoi4File,
-    MeltOptions, PdsDate,
+    file::{Hoi4ParsedBinary, Hoi4ParsedText, Hoi4SliceFileKind},
+    json::{ArrayObjectMode, DuplicateKeyMode, JsonOptions},
+    models::Hoi4Save,
+    BasicTokenResolver, DeserializeReport, Encoding, ErrorCategory, FailedResolveStrategy,
+    Hoi4Date, Hoi4ErrorKind, Hoi4File, I32Hint, KeyHints, MeltConfig, MeltOptions, PdsDate,
+    TokenLoader, TokenSource, TokenTable, TokenTables,
 };
 use jomini::binary::TokenResolver;
 use serde::Deserialize;
-use std::{error::Error, sync::LazyLock};
+use std::{collections::HashMap, error::Error, sync::LazyLock};
 
 mod utils;
 
@@ -178,3 +182,1358 @@ fn test_ironman_roundtrip_with_nulls() -> Result<(), Box<dyn Error>> {
     );
     Ok(())
 }
+
+#[test]
+fn test_balance_of_power_and_special_projects() -> Result<(), Box<dyn Error>> {
+    let data = br#"HOI4txt
+player="GER"
+date="1940.5.10.12"
+countries={
+	FRA={
+		stability=0.5
+	}
+	GER={
+		stability=0.8
+		power_balance={
+			id="GER_army_navy_balance"
+			value=-0.35
+			left_side="army_side"
+			right_side="navy_side"
+			active_range="army_dominance_range"
+			change=0.01
+		}
+		special_project={
+			id="sp_nuclear_reactor"
+			facility=64
+			scientist={
+				id=1204
+				type=73
+			}
+			progress=0.42
+		}
+	}
+}
+"#;
+
+    let file = Hoi4File::from_slice(data)?;
+    let save = file.parse_save(&*TOKENS)?;
+
+    let (_, fra) = &save.countries[0];
+    assert!(fra.power_balances.is_empty());
+    assert!(fra.special_projects.is_empty());
+
+    let (tag, ger) = &save.countries[1];
+    assert_eq!(tag.as_str(), "GER");
+    let balance = &ger.power_balances[0];
+    assert_eq!(balance.id, "GER_army_navy_balance");
+    assert_eq!(balance.value, -0.35);
+    assert_eq!(balance.left_side.as_deref(), Some("army_side"));
+    assert_eq!(balance.right_side.as_deref(), Some("navy_side"));
+    assert_eq!(
+        balance.active_range.as_deref(),
+        Some("army_dominance_range")
+    );
+
+    let project = &ger.special_projects[0];
+    assert_eq!(project.id, "sp_nuclear_reactor");
+    assert_eq!(project.facility, Some(64));
+    assert_eq!(project.scientist.map(|x| x.id), Some(1204));
+    assert_eq!(project.progress, 0.42);
+    assert!(!project.complete);
+    Ok(())
+}
+
+#[test]
+fn test_dynamic_countries() -> Result<(), Box<dyn Error>> {
+    let data = br#"HOI4txt
+player="GER"
+date="1941.1.1.12"
+countries={
+	FRA={
+		stability=0.3
+		collaboration={
+			GER=0.65
+		}
+	}
+	GER={
+		stability=0.8
+	}
+	D01={
+		original_tag=FRA
+		cosmetic_tag="FRA_vichy"
+		color=rgb { 89 111 171 }
+		collaboration_government={
+			master=GER
+			date="1940.6.22.12"
+		}
+	}
+}
+civil_war={
+	original=SPR
+	revolter=D02
+	ideology="fascism"
+}
+"#;
+
+    let file = Hoi4File::from_slice(data)?;
+    let save = file.parse_save(&*TOKENS)?;
+
+    let dynamic: hoi4save::CountryTag = "D01".parse()?;
+    assert!(dynamic.is_dynamic());
+    assert_eq!(save.original_tag(dynamic).as_str(), "FRA");
+    assert_eq!(save.original_tag("GER".parse()?).as_str(), "GER");
+
+    let vichy = save.country(&dynamic).unwrap();
+    assert_eq!(vichy.cosmetic_tag.as_deref(), Some("FRA_vichy"));
+    assert_eq!(vichy.color, vec![89.0, 111.0, 171.0]);
+    let government = vichy.collaboration_government.as_ref().unwrap();
+    assert_eq!(government.master.as_str(), "GER");
+
+    let fra = save.country(&"FRA".parse()?).unwrap();
+    assert_eq!(fra.collaboration[0].0.as_str(), "GER");
+    assert_eq!(fra.collaboration[0].1, 0.65);
+
+    assert_eq!(save.civil_wars.len(), 1);
+    assert_eq!(save.civil_wars[0].original.as_str(), "SPR");
+    assert_eq!(save.civil_wars[0].revolter.as_str(), "D02");
+    Ok(())
+}
+
+#[test]
+fn test_reader_custom_deserialization() -> Result<(), Box<dyn Error>> {
+    #[derive(Deserialize, Debug, Clone)]
+    pub struct CustomHoi4Save {
+        pub player: String,
+        pub date: Hoi4Date,
+    }
+
+    let data = b"HOI4txt\nplayer=\"ITA\"\ndate=\"1937.7.7.12\"\n";
+    let mut file = Hoi4File::from_reader(std::io::Cursor::new(&data[..]))?;
+    assert_eq!(file.encoding(), Encoding::Plaintext);
+
+    let save: CustomHoi4Save = file.parse(&*TOKENS)?;
+    assert_eq!(save.player, String::from("ITA"));
+    assert_eq!(
+        save.date.game_fmt().to_string(),
+        String::from("1937.7.7.12")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_streaming_melt() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let data = utils::test_binary_save();
+    let mut file = Hoi4File::from_reader(utils::Trickle(&data))?;
+    assert_eq!(file.encoding(), Encoding::Binary);
+
+    let mut out = Vec::new();
+    let options = MeltOptions::new().on_failed_resolve(hoi4save::FailedResolveStrategy::Error);
+    file.melt(options, &resolver, &mut out)?;
+
+    let expected = "HOI4txt\nplayer=\"FRA\"\ndate=1936.1.1.12\nsave_version=30\ncountries={\n\tFRA={\n\t\tstability=1\n\t}\n}\n";
+    assert_eq!(std::str::from_utf8(&out)?, expected);
+    Ok(())
+}
+
+#[test]
+fn test_melt_slice_and_reader_match() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let text = b"HOI4txt\nplayer=\"FRA\"\ndate=1936.1.1.12\n";
+    for data in [utils::test_binary_save(), text.to_vec()] {
+        let mut slice = Vec::new();
+        let file = Hoi4File::from_slice(&data)?;
+        file.melt(MeltOptions::new(), &resolver, &mut slice)?;
+
+        let mut reader = Vec::new();
+        let mut file = Hoi4File::from_reader(data.as_slice())?;
+        file.melt(MeltOptions::new(), &resolver, &mut reader)?;
+        assert_eq!(std::str::from_utf8(&reader)?, std::str::from_utf8(&slice)?);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_melt_value_encodings() -> Result<(), Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct Save {
+        stability: f64,
+        war_support: f64,
+        seed: i32,
+    }
+
+    let resolver = utils::test_resolver();
+    let fields = |builder: utils::BinaryBuilder| {
+        builder
+            .key(0x2007)
+            .f64(40960)
+            .key(0x2008)
+            .i32(60759371)
+            .key(0x2009)
+            .i32(60759371)
+            .build()
+    };
+
+    // Before save version 30, decimals are thousandths in an i32
+    let data = fields(
+        utils::BinaryBuilder::new()
+            .key(0x2002)
+            .i32(22)
+            .key(0x2004)
+            .decimal_i32(455),
+    );
+    let file = Hoi4File::from_slice(&data)?;
+    let mut out = Vec::new();
+    file.melt(MeltOptions::new(), &resolver, &mut out)?;
+    let melted = std::str::from_utf8(&out)?;
+    assert!(melted.contains("stability=0.455\n"));
+    assert!(melted.contains("war_support=1.25\n"));
+    assert!(melted.contains("seed=60759371\n"));
+    assert!(melted.contains("start=1936.1.1.12\n"));
+
+    let save: Save = file.parse(&resolver)?;
+    assert!((save.stability - 0.455).abs() < 1e-6);
+    assert_eq!(save.war_support, 1.25);
+    assert_eq!(save.seed, 60759371);
+
+    // From save version 30, the value is an i64 scaled by 100000
+    let data = fields(
+        utils::BinaryBuilder::new()
+            .key(0x2002)
+            .i32(30)
+            .key(0x2004)
+            .decimal_i64(4_500_000),
+    );
+    let file = Hoi4File::from_slice(&data)?;
+    let mut out = Vec::new();
+    file.melt(MeltOptions::new(), &resolver, &mut out)?;
+    let melted = std::str::from_utf8(&out)?;
+    assert!(melted.contains("stability=45\n"));
+    assert!(melted.contains("war_support=1.25\n"));
+    Ok(())
+}
+
+#[test]
+fn test_parsed_binary() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let data = utils::test_binary_save();
+    let binary = Hoi4ParsedBinary::from_slice(&data, &resolver)?;
+    let reader = binary.reader();
+
+    let (key, value) = reader.fields().next().unwrap();
+    assert_eq!(key.read_str(), "player");
+    assert_eq!(value.read_str().as_deref(), Some("FRA"));
+
+    let (_, countries) = reader
+        .fields()
+        .find(|(key, _)| key.read_str() == "countries")
+        .unwrap();
+    let (tag, country) = countries.read_object().unwrap().fields().next().unwrap();
+    assert_eq!(tag.read_str(), "FRA");
+    let (_, stability) = country.read_object().unwrap().fields().next().unwrap();
+    assert_eq!(stability.read_f64(), Some(1.0));
+
+    let json = reader.json().to_string();
+    assert_eq!(
+        json,
+        r#"{"player":"FRA","ironman":1,"date":"1936.1.1.12","save_version":30,"countries":{"FRA":{"stability":1}}}"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_melt_i32_hints() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let data = utils::test_binary_save();
+    let file = Hoi4File::from_slice(&data)?;
+
+    let mut out = Vec::new();
+    let hints = KeyHints::new().known_numbers(["date"]);
+    let config = MeltConfig::new(MeltOptions::new()).hints(hints.clone());
+    file.melt(config, &resolver, &mut out)?;
+    assert!(std::str::from_utf8(&out)?.contains("\ndate=60759371\n"));
+
+    // Known keys are added to the defaults
+    assert_eq!(hints.hint("random_seed"), I32Hint::Number);
+    assert_eq!(hints.hint("start"), I32Hint::Heuristic);
+
+    // The same hints apply when converting to JSON
+    let parsed = Hoi4ParsedBinary::from_slice(&data, &resolver)?;
+    let options = JsonOptions::new().with_hints(hints.clone());
+    let json = parsed
+        .reader()
+        .json()
+        .with_options(options.clone())
+        .to_string();
+    assert!(json.contains(r#""date":60759371"#));
+
+    assert_eq!(utils::reader_json(&data, options, &resolver)?, json);
+
+    // The callback takes precedence over the known keys
+    let mut out = Vec::new();
+    let hints = hints.i32_hint(|key| (key == "date").then_some(I32Hint::Heuristic));
+    let config = MeltConfig::new(MeltOptions::new()).hints(hints);
+    Hoi4File::from_slice(&data)?.melt(config, &resolver, &mut out)?;
+    assert!(std::str::from_utf8(&out)?.contains("\ndate=1936.1.1.12\n"));
+    Ok(())
+}
+
+#[test]
+fn test_melt_path_filters() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let data = utils::test_binary_save();
+    let file = Hoi4File::from_slice(&data)?;
+
+    // The save version is dropped but still decides how stability is read
+    let mut out = Vec::new();
+    let config = MeltConfig::new(MeltOptions::new()).include("countries.*.stability");
+    file.melt(config, &resolver, &mut out)?;
+    let expected = "HOI4txt\ncountries={\n\tFRA={\n\t\tstability=1\n\t}\n}\n";
+    assert_eq!(std::str::from_utf8(&out)?, expected);
+
+    let mut out = Vec::new();
+    let config = MeltConfig::new(MeltOptions::new())
+        .exclude("countries.*.stability")
+        .exclude("date");
+    file.melt(config, &resolver, &mut out)?;
+    let melted = std::str::from_utf8(&out)?;
+    assert!(melted.starts_with("HOI4txt\nplayer=\"FRA\"\nsave_version=30\ncountries={"));
+    assert!(!melted.contains("stability"));
+    Ok(())
+}
+
+#[test]
+fn test_melt_source_map() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let data = utils::test_binary_save();
+    let file = Hoi4File::from_slice(&data)?;
+
+    let mut out = Vec::new();
+    let doc = file.melt(MeltOptions::new(), &resolver, &mut out)?;
+    assert!(doc.source_map().is_empty());
+
+    let mut out = Vec::new();
+    let options = MeltOptions::new().source_map(true);
+    let doc = file.melt(options, &resolver, &mut out)?;
+    let body = &out[b"HOI4txt\n".len()..];
+    let body_data = &data[b"HOI4bin".len()..];
+    for mapping in doc.source_map() {
+        let id = &body_data[mapping.input_offset..mapping.input_offset + 2];
+        assert_eq!(u16::from_le_bytes([id[0], id[1]]), mapping.token_id);
+    }
+
+    let stability = doc
+        .source_map()
+        .iter()
+        .find(|x| x.token_id == 0x2004)
+        .unwrap();
+    let text = &body[stability.output_start..stability.output_end];
+    assert_eq!(std::str::from_utf8(text)?, "stability");
+    assert_eq!(stability.line, 6);
+
+    let value = doc
+        .source_map()
+        .iter()
+        .find(|x| x.token_id == 0x000d)
+        .unwrap();
+    assert_eq!(&body[value.output_start..value.output_end], b"1");
+    Ok(())
+}
+
+#[test]
+fn test_melt_statistics() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let data = utils::test_binary_save();
+    let file = Hoi4File::from_slice(&data)?;
+
+    let doc = file.melt(MeltOptions::new(), &resolver, std::io::sink())?;
+    assert!(doc.statistics().is_none());
+
+    let options = MeltOptions::new().statistics(true);
+    let doc = file.melt(options, &resolver, std::io::sink())?;
+    let stats = doc.statistics().unwrap();
+    assert_eq!(stats.save_version, Some(30));
+    assert!(stats.new_save_format);
+    assert_eq!(stats.known_dates, 1);
+    assert_eq!(stats.heuristic_dates, 0);
+    assert_eq!(stats.token_counts.get(&0x000c), Some(&2));
+    assert_eq!(stats.token_counts.get(&0x000d), Some(&1));
+    assert_eq!(stats.token_counts.get(&0x0003), Some(&2));
+    assert_eq!(stats.token_counts.get(&0x2005), None);
+
+    // The save version is reported when filtered out
+    let config = MeltConfig::new(MeltOptions::new().statistics(true)).include("countries");
+    let doc = file.melt(config, &resolver, std::io::sink())?;
+    let stats = doc.statistics().unwrap();
+    assert_eq!(stats.save_version, Some(30));
+    assert_eq!(stats.token_counts.get(&0x2000), None);
+    assert_eq!(stats.token_counts.get(&0x2003), Some(&1));
+    Ok(())
+}
+
+#[test]
+fn test_melt_unknown_token_context() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let mut data = utils::test_binary_save();
+
+    // version={ 0x3000=7 0x3001=0x3002 } version={ 0x3002 }
+    let tokens: [u16; 16] = [
+        0x2006, 0x0001, 0x0003, 0x3000, 0x0001, 0x000c, 0x0007, 0x0000, 0x3001, 0x0001, 0x3002,
+        0x0004, 0x2006, 0x0001, 0x0003, 0x3002,
+    ];
+    data.extend(tokens.iter().flat_map(|x| x.to_le_bytes()));
+    data.extend_from_slice(&0x0004u16.to_le_bytes());
+    let file = Hoi4File::from_slice(&data)?;
+
+    let doc = file.melt(MeltOptions::new(), &resolver, std::io::sink())?;
+    assert!(doc.unknown_token_context().is_empty());
+
+    let options = MeltOptions::new().unknown_token_context(true);
+    let doc = file.melt(options, &resolver, std::io::sink())?;
+    let contexts = doc.unknown_token_context();
+    assert_eq!(contexts.len(), 3);
+
+    let context = &contexts[&0x3000];
+    assert_eq!(context.paths.iter().collect::<Vec<_>>(), ["version"]);
+    assert_eq!((context.key_count, context.value_count), (1, 0));
+    assert_eq!(context.value_types.iter().collect::<Vec<_>>(), [&0x000c]);
+    assert_eq!(context.sample_value.as_deref(), Some("7"));
+
+    let context = &contexts[&0x3001];
+    assert_eq!(context.sample_value.as_deref(), Some("__unknown_0x3002"));
+
+    let context = &contexts[&0x3002];
+    assert_eq!((context.key_count, context.value_count), (0, 2));
+    let paths: Vec<_> = context.paths.iter().map(String::as_str).collect();
+    assert_eq!(paths, ["version", "version.__unknown_0x3001"]);
+    Ok(())
+}
+
+#[test]
+fn test_parse_with_report() -> Result<(), Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct Save {
+        player: String,
+        version: Option<Version>,
+    }
+
+    #[derive(Deserialize)]
+    struct Version {
+        stability: i32,
+        seed: String,
+    }
+
+    let resolver = utils::test_resolver();
+    let mut data = utils::test_binary_save();
+    let file = Hoi4File::from_slice(&data)?;
+    let (save, report) = file.parse_with_report::<Save, _>(&resolver)?;
+    assert_eq!(save.player, "FRA");
+    assert!(save.version.is_none());
+    assert!(report.is_empty());
+
+    // version={ 0x3000=7 stability=2 seed=0x3003 } 0x3001=0x3002
+    let tokens: [u16; 19] = [
+        0x2006, 0x0001, 0x0003, 0x3000, 0x0001, 0x000c, 0x0007, 0x0000, 0x2004, 0x0001, 0x000c,
+        0x0002, 0x0000, 0x2008, 0x0001, 0x3003, 0x0004, 0x3001, 0x0001,
+    ];
+    data.extend(tokens.iter().flat_map(|x| x.to_le_bytes()));
+    data.extend_from_slice(&0x3002u16.to_le_bytes());
+
+    // The value of an unknown key is skipped without being resolved
+    let check = |save: Save, report: DeserializeReport| {
+        let version = save.version.unwrap();
+        assert_eq!(version.stability, 2);
+        assert_eq!(version.seed, "__internal_identifier_ignore");
+        assert_eq!(
+            report.unknown_tokens().collect::<Vec<_>>(),
+            [0x3000, 0x3001, 0x3003]
+        );
+        let paths: Vec<_> = report.paths(0x3000).unwrap().iter().collect();
+        assert_eq!(paths, ["version"]);
+        let paths: Vec<_> = report.paths(0x3001).unwrap().iter().collect();
+        assert_eq!(paths, [""]);
+        let paths: Vec<_> = report.paths(0x3003).unwrap().iter().collect();
+        assert_eq!(paths, ["version.seed"]);
+    };
+
+    let file = Hoi4File::from_slice(&data)?;
+    let (save, report) = file.parse_with_report::<Save, _>(&resolver)?;
+    check(save, report);
+
+    let mut file = Hoi4File::from_reader(data.as_slice())?;
+    let (save, report) = file.parse_with_report::<Save, _>(&resolver)?;
+    check(save, report);
+    Ok(())
+}
+
+#[test]
+fn test_error_locations() -> Result<(), Box<dyn Error>> {
+    #[derive(Debug, Deserialize)]
+    struct Save {
+        #[allow(dead_code)]
+        countries: HashMap<String, Country>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Country {
+        #[allow(dead_code)]
+        stability: bool,
+    }
+
+    let resolver = utils::test_resolver();
+    let data = utils::test_binary_save();
+    let file = Hoi4File::from_slice(&data)?;
+    let err = file.parse::<Save, _>(&resolver).unwrap_err();
+    let location = err.location().unwrap();
+    assert_eq!(location.path.as_deref(), Some("countries.FRA.stability"));
+    assert_eq!(location.offset, Some(58));
+
+    // A reader can't be deserialized again to find the path
+    let mut reader = Hoi4File::from_reader(data.as_slice())?;
+    let err = reader.parse::<Save, _>(&resolver).unwrap_err();
+    assert_eq!(err.location().map(|x| x.path.clone()), Some(None));
+
+    let options = MeltOptions::new().on_failed_resolve(FailedResolveStrategy::Error);
+    let mut partial = resolver.clone();
+    partial.remove(&0x2004);
+    let err = file.melt(options, &partial, std::io::sink()).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        Hoi4ErrorKind::UnknownToken { token_id: 0x2004 }
+    ));
+    let location = err.location().unwrap();
+    assert_eq!(
+        location.path.as_deref(),
+        Some("countries.FRA.__unknown_0x2004")
+    );
+    assert_eq!(location.offset, Some(58));
+
+    // Truncated within the stability value
+    let truncated = &data[..data.len() - 6];
+    let file = Hoi4File::from_slice(truncated)?;
+    let err = file.melt(options, &resolver, std::io::sink()).unwrap_err();
+    assert!(matches!(err.kind(), Hoi4ErrorKind::Eof));
+    let location = err.location().unwrap();
+    assert_eq!(location.path.as_deref(), Some("countries.FRA.stability"));
+    assert_eq!(location.offset, Some(62));
+    assert!(err.to_string().contains("countries.FRA.stability"));
+
+    // Keys are tracked when recovering, and only the offset is known when
+    // melting a reader
+    let mut file = Hoi4File::from_reader(truncated)?;
+    let err = file.melt(options, &resolver, std::io::sink()).unwrap_err();
+    let location = err.location().unwrap();
+    assert_eq!(location.path, None);
+    assert_eq!(location.offset, Some(62));
+    Ok(())
+}
+
+#[test]
+fn test_error_categories() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let data = utils::test_binary_save();
+
+    // Cut short before the final close token, which is only checked by a
+    // strict melt
+    let file = Hoi4File::from_slice(&data[..data.len() - 2])?;
+    file.melt(MeltOptions::new(), &resolver, std::io::sink())?;
+    let strict = MeltOptions::new().strict(true);
+    let err = file.melt(strict, &resolver, std::io::sink()).unwrap_err();
+    assert!(matches!(err.kind(), Hoi4ErrorKind::Eof));
+    assert_eq!(err.category(), ErrorCategory::Truncated);
+    assert!(err.is_recoverable());
+
+    // Cut short within a value
+    let err = file.parse_save(&resolver).unwrap_err();
+    assert_eq!(err.category(), ErrorCategory::Truncated);
+
+    let mut stray = data.clone();
+    stray.extend_from_slice(&0x0004u16.to_le_bytes());
+    let file = Hoi4File::from_slice(&stray)?;
+    let err = file
+        .melt(MeltOptions::new(), &resolver, std::io::sink())
+        .unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        Hoi4ErrorKind::UnbalancedContainers { .. }
+    ));
+    assert_eq!(err.location().unwrap().offset, Some(76));
+
+    let err = file.melt(strict, &resolver, std::io::sink()).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        Hoi4ErrorKind::UnbalancedContainers { offset: 76 }
+    ));
+    assert_eq!(err.category(), ErrorCategory::InvalidStructure);
+    assert!(!err.is_recoverable());
+
+    // The stray close token is rejected when parsing and converting to JSON
+    let Err(err) = Hoi4ParsedBinary::from_slice(&stray, &resolver) else {
+        panic!("expected a stray close token to be an error");
+    };
+    assert!(matches!(
+        err.kind(),
+        Hoi4ErrorKind::UnbalancedContainers { offset: 76 }
+    ));
+
+    let err = utils::reader_json(&stray, JsonOptions::new(), &resolver).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        Hoi4ErrorKind::UnbalancedContainers { offset: 76 }
+    ));
+
+    let file = Hoi4File::from_slice(&data)?;
+    let options = MeltOptions::new().on_failed_resolve(FailedResolveStrategy::Error);
+    let err = file
+        .melt(options, HashMap::<u16, &str>::new(), std::io::sink())
+        .unwrap_err();
+    assert_eq!(err.category(), ErrorCategory::UnknownToken);
+    assert!(err.is_recoverable());
+
+    // Strings in a save are decoded lossily, so only token tables reject
+    // invalid utf-8
+    let text = Hoi4ParsedText::from_raw(b"player=\"F\xffA\"")?;
+    let player = text.reader().fields().next().unwrap().2.read_string()?;
+    assert_eq!(player, "F\u{fffd}A");
+
+    let err = TokenTable::from_text_lines(&b"0x2000 player\n0x2001 F\xffA\n"[..]).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        Hoi4ErrorKind::InvalidUtf8 { offset: 22 }
+    ));
+    assert_eq!(err.category(), ErrorCategory::InvalidUtf8);
+
+    let err = Hoi4File::from_slice(b"garbage").unwrap_err();
+    assert_eq!(err.category(), ErrorCategory::UnsupportedFormat);
+    Ok(())
+}
+
+#[test]
+fn test_melt_recover() -> Result<(), Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct Save {
+        player: String,
+        countries: HashMap<String, HashMap<String, f64>>,
+    }
+
+    let resolver = utils::test_resolver();
+    let data = utils::test_binary_save();
+    let options = MeltOptions::new().recover(true);
+
+    let file = Hoi4File::from_slice(&data)?;
+    let doc = file.melt(options, &resolver, std::io::sink())?;
+    assert!(doc.truncation().is_none());
+
+    // Cut short within the stability value
+    let file = Hoi4File::from_slice(&data[..data.len() - 6])?;
+    let mut out = Vec::new();
+    let doc = file.melt(options, &resolver, &mut out)?;
+    let truncation = doc.truncation().unwrap();
+    assert_eq!(truncation.offset, 58);
+    assert_eq!(truncation.path, "countries.FRA.stability");
+    assert_eq!(truncation.closed, 2);
+
+    let melted = Hoi4File::from_slice(&out)?;
+    let save: Save = melted.parse(&resolver)?;
+    assert_eq!(save.player, "FRA");
+    assert!(save.countries["FRA"].is_empty());
+
+    let (save, truncation) = file.parse_recovered::<Save, _>(&resolver)?;
+    assert!(save.countries["FRA"].is_empty());
+    assert_eq!(truncation.unwrap().closed, 2);
+
+    // Cut short before the final close token
+    let file = Hoi4File::from_slice(&data[..data.len() - 2])?;
+    let (save, truncation) = file.parse_recovered::<Save, _>(&resolver)?;
+    assert_eq!(save.countries["FRA"]["stability"], 1.0);
+    let truncation = truncation.unwrap();
+    assert_eq!(truncation.offset, 74);
+    assert_eq!(truncation.path, "countries");
+    assert_eq!(truncation.closed, 1);
+    Ok(())
+}
+
+#[test]
+fn test_melt_recover_long_field() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let key = vec![b'k'; usize::from(u16::MAX)];
+    let value = vec![b'v'; usize::from(u16::MAX)];
+    let data = utils::BinaryBuilder::new()
+        .key(0x2000)
+        .string(b"FRA")
+        .string(&key)
+        .token(0x0001)
+        .string(&value)
+        .build();
+    let options = MeltOptions::new().recover(true);
+
+    let mut file = Hoi4File::from_reader(data.as_slice())?;
+    let mut out = Vec::new();
+    let doc = file.melt(options, &resolver, &mut out)?;
+    assert!(doc.truncation().is_none());
+    let expected = [&key[..], b"=\"", &value[..], b"\""].concat();
+    assert!(out.windows(expected.len()).any(|x| x == expected));
+
+    // Cut short within the value
+    let mut file = Hoi4File::from_reader(&data[..data.len() - 1])?;
+    let doc = file.melt(options, &resolver, std::io::sink())?;
+    assert_eq!(doc.truncation().unwrap().offset, 11);
+    Ok(())
+}
+
+#[test]
+fn test_infer_tokens() -> Result<(), Box<dyn Error>> {
+    let binary = utils::test_binary_save();
+
+    // The text save is not ironman, so it is missing the ironman field
+    let text = b"HOI4txt\nplayer=\"FRA\"\ndate=1936.1.1.12\nsave_version=30\ncountries={\n\tFRA={\n\t\tstability=1\n\t}\n}\n";
+    let resolver: HashMap<u16, String> = HashMap::new();
+    let inferred = hoi4save::infer_tokens(&binary, text, &resolver)?;
+    let names: Vec<_> = inferred
+        .tokens()
+        .iter()
+        .map(|x| (x.id, x.name.as_str()))
+        .collect();
+    let expected: Vec<_> = utils::TEST_TOKENS
+        .iter()
+        .copied()
+        .filter(|(id, _)| matches!(id, 0x2000..=0x2004))
+        .collect();
+    assert_eq!(names, expected);
+    assert!(inferred.tokens().iter().all(|x| x.confidence == 1.0));
+
+    let mut out = Vec::new();
+    inferred.write_text_lines(&mut out, 0.5)?;
+    let resolver = BasicTokenResolver::from_text_lines(out.as_slice())?;
+    assert_eq!(resolver.resolve(0x2004), Some("stability"));
+
+    // Known tokens are not proposed
+    let resolver: HashMap<u16, &str> = HashMap::from([(0x2000, "player")]);
+    let inferred = hoi4save::infer_tokens(&binary, text, &resolver)?;
+    assert!(inferred.tokens().iter().all(|x| x.id != 0x2000));
+    Ok(())
+}
+
+#[test]
+fn test_token_tables() -> Result<(), Box<dyn Error>> {
+    let lines: String = utils::TEST_TOKENS
+        .iter()
+        .map(|(id, name)| format!("0x{:x} {}\n", id, name))
+        .collect();
+    let table = TokenTable::from_text_lines(lines.as_bytes())?;
+    assert_eq!(table.len(), utils::TEST_TOKENS.len());
+
+    let data = utils::test_binary_save();
+    let file = Hoi4File::from_slice(&data)?;
+    let save = file.parse_save(&table)?;
+    assert_eq!(save.player, "FRA");
+    let options = MeltOptions::new().on_failed_resolve(hoi4save::FailedResolveStrategy::Error);
+    file.melt(options, &table, std::io::sink())?;
+
+    let mut patch = TokenTable::new();
+    patch.insert(0x2000, "player_tag");
+    patch.insert(0x3000, "new_token");
+    let diff = table.diff(&patch);
+    assert_eq!(diff.added, [(0x3000, String::from("new_token"))]);
+    assert_eq!(diff.removed.len(), utils::TEST_TOKENS.len() - 1);
+    assert_eq!(diff.changed.len(), 1);
+
+    let mut merged = table.clone();
+    let conflicts = merged.merge(&patch);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(
+        (conflicts[0].ours.as_str(), conflicts[0].theirs.as_str()),
+        ("player", "player_tag")
+    );
+    assert_eq!(merged.get(0x2000), Some("player"));
+    assert_eq!(merged.get(0x3000), Some("new_token"));
+
+    let mut out = Vec::new();
+    merged.write_text_lines(&mut out)?;
+    assert_eq!(TokenTable::from_text_lines(out.as_slice())?, merged);
+
+    let mut tables = TokenTables::new();
+    tables.insert(22, Some("1.12"), patch);
+    tables.insert(30, Some("1.14"), table);
+    let summary = file.summary(tables.merged())?;
+    assert_eq!(summary.save_version, Some(30));
+    let selected = tables.for_summary(&summary).unwrap();
+    assert_eq!(selected.get(0x2000), Some("player"));
+    assert!(tables.for_save_version(10).is_none());
+    assert_eq!(
+        tables
+            .for_game_version("Collie v1.12.14")
+            .unwrap()
+            .get(0x2000),
+        Some("player_tag")
+    );
+
+    let err = TokenTable::from_text_lines(&b"0x2000 player\nplayer\n"[..]).unwrap_err();
+    assert!(err.to_string().contains("line 2"));
+    Ok(())
+}
+
+#[test]
+fn test_token_loader() -> Result<(), Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("hoi4save-tokens-{}", std::process::id()));
+    let config = dir.join("config");
+    std::fs::create_dir_all(config.join("hoi4save"))?;
+
+    let table: TokenTable = utils::TEST_TOKENS
+        .iter()
+        .map(|(id, name)| (*id, name.to_string()))
+        .collect();
+    let mut binary = Vec::new();
+    table.write_binary(&mut binary)?;
+    assert_eq!(TokenTable::from_bytes(&binary)?, table);
+    assert!(TokenTable::from_bytes(&binary[..binary.len() - 1]).is_err());
+
+    let bin_path = config.join("hoi4save").join("hoi4.bin");
+    std::fs::write(&bin_path, &binary)?;
+    let text_path = dir.join("tokens.txt");
+    let mut text = Vec::new();
+    table.write_text_lines(&mut text)?;
+    std::fs::write(&text_path, &text)?;
+
+    // The environment is injected rather than set, as tests run in parallel
+    let loader = TokenLoader::new()
+        .env_path(None)
+        .config_dir(Some(config.clone()));
+    let missing = dir.join("missing.txt");
+    let (tokens, source) = loader.clone().path(&missing).load()?;
+    assert_eq!(source, TokenSource::ConfigDir(bin_path));
+    assert_eq!(tokens, table);
+
+    let (_, source) = loader.clone().path(&text_path).load()?;
+    assert_eq!(source, TokenSource::Path(text_path.clone()));
+
+    let env_loader = loader.clone().env_path(Some(text_path.clone()));
+    let (tokens, source) = env_loader.path(&missing).load()?;
+    assert_eq!(source, TokenSource::EnvVar(text_path));
+    assert_eq!(tokens, table);
+
+    assert!(loader
+        .clone()
+        .env_path(Some(missing.clone()))
+        .load()
+        .is_err());
+
+    let loader = loader.config_dir(Some(dir.join("empty")));
+    let (tokens, source) = loader.path(&missing).load()?;
+    assert_eq!(source, TokenSource::NotFound);
+    assert!(tokens.is_empty());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_binary_json_stream() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let mut data = utils::test_binary_save();
+
+    // ironman=2 version={ { player="GER" } 5 ironman=3 {} }
+    let tokens: [u16; 7] = [0x2005, 0x0001, 0x000c, 0x0002, 0x0000, 0x2006, 0x0001];
+    data.extend(tokens.iter().flat_map(|x| x.to_le_bytes()));
+    data.extend_from_slice(&[0x03, 0x00, 0x03, 0x00, 0x00, 0x20, 0x01, 0x00]);
+    data.extend_from_slice(&[0x0f, 0x00, 0x03, 0x00, b'G', b'E', b'R', 0x04, 0x00]);
+    data.extend_from_slice(&[0x0c, 0x00, 0x05, 0x00, 0x00, 0x00]);
+    data.extend_from_slice(&[0x05, 0x20, 0x01, 0x00, 0x0c, 0x00, 0x03, 0x00, 0x00, 0x00]);
+    data.extend_from_slice(&[0x03, 0x00, 0x04, 0x00, 0x04, 0x00]);
+
+    let parsed = Hoi4ParsedBinary::from_slice(&data, &resolver)?;
+    let modes = [
+        DuplicateKeyMode::Preserve,
+        DuplicateKeyMode::Group,
+        DuplicateKeyMode::KeyValuePairs,
+    ];
+
+    let array_modes = [ArrayObjectMode::SingleField, ArrayObjectMode::Merge];
+    for (mode, array_mode) in modes.into_iter().flat_map(|x| array_modes.map(|y| (x, y))) {
+        for pretty in [false, true] {
+            let options = JsonOptions::new()
+                .with_prettyprint(pretty)
+                .with_duplicate_keys(mode)
+                .with_array_objects(array_mode);
+            let expected = parsed
+                .reader()
+                .json()
+                .with_options(options.clone())
+                .to_vec();
+
+            let json = utils::reader_json(&data, options, &resolver)?;
+            assert_eq!(json, String::from_utf8(expected)?);
+        }
+    }
+
+    assert!(utils::reader_json(&data, JsonOptions::new(), &resolver)?
+        .ends_with(r#""ironman":2,"version":[{"player":"GER"},5,{"ironman":3},{}]}"#));
+
+    // A save that ends inside of an object is an error
+    let truncated = &data[..data.len() - 2];
+    assert!(utils::reader_json(truncated, JsonOptions::new(), &resolver).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_binary_json_array_objects() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+
+    // version={ { player="GER" } ironman=1 save_version=2 5 ironman=3 }
+    let data = utils::BinaryBuilder::new()
+        .key(0x2006)
+        .open()
+        .open()
+        .key(0x2000)
+        .string(b"GER")
+        .close()
+        .key(0x2005)
+        .i32(1)
+        .key(0x2002)
+        .i32(2)
+        .i32(5)
+        .key(0x2005)
+        .i32(3)
+        .close()
+        .build();
+
+    let cases = [
+        (
+            ArrayObjectMode::SingleField,
+            r#"{"version":[{"player":"GER"},{"ironman":1},{"save_version":2},5,{"ironman":3}]}"#,
+        ),
+        (
+            ArrayObjectMode::Merge,
+            r#"{"version":[{"player":"GER"},{"ironman":1,"save_version":2},5,{"ironman":3}]}"#,
+        ),
+    ];
+
+    let parsed = Hoi4ParsedBinary::from_slice(&data, &resolver)?;
+    for (mode, expected) in cases {
+        let options = JsonOptions::new().with_array_objects(mode);
+        let json = parsed
+            .reader()
+            .json()
+            .with_options(options.clone())
+            .to_string();
+        assert_eq!(json, expected);
+
+        assert_eq!(utils::reader_json(&data, options, &resolver)?, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_binary_json_rgb() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let data = utils::BinaryBuilder::new()
+        .key(0x200a)
+        .token(0x0243)
+        .open()
+        .i32(89)
+        .i32(111)
+        .i32(171)
+        .close()
+        .key(0x2005)
+        .i32(1)
+        .key(0x2006)
+        .open()
+        .token(0x0243)
+        .open()
+        .i32(1)
+        .i32(2)
+        .i32(3)
+        .close()
+        .close()
+        .build();
+
+    let expected = r#"{"color":{"rgb":[89,111,171]},"ironman":1,"version":[{"rgb":[1,2,3]}]}"#;
+    let parsed = Hoi4ParsedBinary::from_slice(&data, &resolver)?;
+    assert_eq!(parsed.reader().json().to_string(), expected);
+
+    assert_eq!(
+        utils::reader_json(&data, JsonOptions::new(), &resolver)?,
+        expected
+    );
+    Ok(())
+}
+
+#[test]
+fn test_summary() -> Result<(), Box<dyn Error>> {
+    // The summary stops at the first object, so a truncated body is fine
+    let data = b"HOI4txt\nplayer=\"GER\"\ndate=\"1939.9.1.12\"\nversion=\"Collie v1.10.8\"\nsave_version=22\ncountries={\n\tGER={";
+    let file = Hoi4File::from_slice(data)?;
+    let summary = file.summary(&*TOKENS)?;
+    assert_eq!(summary.player.as_deref(), Some("GER"));
+    assert_eq!(summary.date, Some(Hoi4Date::from_ymdh(1939, 9, 1, 12)));
+    assert_eq!(summary.version.as_deref(), Some("Collie v1.10.8"));
+    assert_eq!(summary.save_version, Some(22));
+
+    let resolver = utils::test_resolver();
+    let data = utils::test_binary_save();
+    let file = Hoi4File::from_reader(data.as_slice())?;
+    let summary = file.summary(&resolver)?;
+    assert_eq!(summary.player.as_deref(), Some("FRA"));
+    assert_eq!(summary.date, Some(Hoi4Date::from_ymdh(1936, 1, 1, 12)));
+    assert_eq!(summary.save_version, Some(30));
+    assert_eq!(summary.version, None);
+    Ok(())
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_compressed_containers() -> Result<(), Box<dyn Error>> {
+    use flate2::{
+        write::{GzEncoder, ZlibEncoder},
+        Compression,
+    };
+    use hoi4save::Container;
+    use std::io::Write;
+
+    let data = b"HOI4txt\nplayer=\"FRA\"\ndate=\"1936.1.1.12\"\n";
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(data)?;
+    let gzip = gzip.finish()?;
+
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+    zlib.write_all(data)?;
+    let zlib = zlib.finish()?;
+
+    let mut zip = Vec::new();
+    let mut archive = rawzip::ZipArchiveWriter::new(&mut zip);
+    archive.new_dir("saves/").create()?;
+    let (mut entry, config) = archive
+        .new_file("saves/autosave.hoi4")
+        .compression_method(rawzip::CompressionMethod::Deflate)
+        .start()?;
+    let mut writer = config.wrap(flate2::write::DeflateEncoder::new(
+        &mut entry,
+        Compression::default(),
+    ));
+    writer.write_all(data)?;
+    let (encoder, output) = writer.finish()?;
+    encoder.finish()?;
+    entry.finish(output)?;
+    archive.finish()?;
+
+    let cases = [
+        (&data[..], Container::Raw),
+        (&gzip[..], Container::Gzip),
+        (&zlib[..], Container::Zlib),
+        (&zip[..], Container::Zip),
+    ];
+
+    for (input, container) in cases {
+        let mut buf = Vec::new();
+        let file = Hoi4File::from_compressed_slice(input, &mut buf)?;
+        assert_eq!(file.container(), container);
+        assert_eq!(file.encoding(), Encoding::Plaintext);
+        let save = file.parse_save(&*TOKENS)?;
+        assert_eq!(save.player, "FRA");
+
+        let mut file = Hoi4File::from_compressed_reader(input)?;
+        assert_eq!(file.container(), container);
+        let save = file.parse_save(&*TOKENS)?;
+        assert_eq!(save.date, Hoi4Date::from_ymdh(1936, 1, 1, 12));
+    }
+
+    // Decompressing past the limit is an error
+    for input in [&gzip[..], &zlib[..]] {
+        let mut reader = hoi4save::Decompressor::with_limit(input, 16)?;
+        assert!(std::io::copy(&mut reader, &mut std::io::sink()).is_err());
+    }
+
+    let Err(err) = hoi4save::Decompressor::with_limit(&zip[..], 16) else {
+        panic!("expected the zip to exceed the limit");
+    };
+    assert!(matches!(
+        err.kind(),
+        hoi4save::Hoi4ErrorKind::DecompressedSizeLimit { limit: 16 }
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mmap_file() -> Result<(), Box<dyn Error>> {
+    let path = std::env::temp_dir().join(format!("hoi4save-mmap-{}.hoi4", std::process::id()));
+    std::fs::write(&path, utils::test_binary_save())?;
+    let file = std::fs::File::open(&path)?;
+
+    // Safety: the file is private to this test and not modified
+    let mmap = unsafe { Hoi4File::from_mmap(&file)? };
+    let resolver = utils::test_resolver();
+    let save = mmap.file().parse_save(&resolver)?;
+    assert_eq!(save.player, "FRA");
+
+    let mut out = Vec::new();
+    mmap.file().melt(MeltOptions::new(), &resolver, &mut out)?;
+    drop(mmap);
+    std::fs::remove_file(&path)?;
+
+    // Map the melted output to exercise the text path
+    std::fs::write(&path, &out)?;
+    let file = std::fs::File::open(&path)?;
+    let mmap = unsafe { Hoi4File::from_mmap(&file)? };
+    assert_eq!(mmap.file().encoding(), Encoding::Plaintext);
+    let text = mmap.parse_text()?;
+    let reader = text.reader();
+    let player = reader
+        .fields()
+        .find(|(key, _, _)| key.read_str() == "player");
+    assert_eq!(player.unwrap().2.read_string()?, "FRA");
+    drop(mmap);
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_async_file() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let data = utils::test_binary_save();
+
+    let file = Hoi4File::from_async_reader(data.as_slice()).await?;
+    assert_eq!(file.encoding(), Encoding::Binary);
+    let save = file.parse_save(&resolver).await?;
+    assert_eq!(save.player, "FRA");
+
+    let file = Hoi4File::from_async_reader(data.as_slice()).await?;
+    let mut melted = Vec::new();
+    file.melt(MeltOptions::new(), resolver.clone(), &mut melted)
+        .await?;
+    assert!(melted.starts_with(b"HOI4txt\nplayer=\"FRA\""));
+
+    let mut expected = Vec::new();
+    Hoi4File::from_slice(&data)?.melt(MeltOptions::new(), &resolver, &mut expected)?;
+    assert_eq!(melted, expected);
+
+    // A save that ends inside of an object is an error for a strict melt
+    let truncated = &data[..data.len() - 2];
+    let file = Hoi4File::from_async_reader(truncated).await?;
+    let strict = MeltOptions::new().strict(true);
+    let result = file.melt(strict, resolver.clone(), tokio::io::sink()).await;
+    assert!(result.is_err());
+
+    let file = Hoi4File::from_async_reader(melted.as_slice()).await?;
+    assert_eq!(file.encoding(), Encoding::Plaintext);
+    let mut out = Vec::new();
+    file.melt(MeltOptions::new(), resolver.clone(), &mut out)
+        .await?;
+    assert_eq!(out, melted);
+
+    let file = Hoi4File::from_async_reader(melted.as_slice()).await?;
+    let save = file.parse_save(&resolver).await?;
+    assert_eq!(save.date, Hoi4Date::from_ymdh(1936, 1, 1, 12));
+    Ok(())
+}
+
+#[test]
+fn test_freeze_roundtrip() -> Result<(), Box<dyn Error>> {
+    let resolver = utils::test_resolver();
+    let names: HashMap<&str, u16> = utils::TEST_TOKENS
+        .iter()
+        .map(|&(id, name)| (name, id))
+        .collect();
+
+    let text = b"HOI4txt\nplayer=\"FRA\"\ndate=1936.1.1.12\nsave_version=22\nversion=\"Collie v1.10.8\"\ncountries={\n\tFRA={\n\t\tstability=0.455\n\t\twar_support=1.25\n\t\tflags={ yes no }\n\t\tideology=fascism\n\t\tcolor=rgb { 89 111 171 }\n\t\tstart=1936.1.1.1\n\t\t__unknown_0x3000=2\n\t}\n}\n";
+    let parsed = Hoi4ParsedText::from_slice(text)?;
+    let mut binary = Vec::new();
+    parsed.freeze(&names, &mut binary)?;
+    assert!(binary.starts_with(b"HOI4bin"));
+
+    let file = Hoi4File::from_slice(&binary)?;
+    let save = file.parse_save(&resolver)?;
+    assert_eq!(save.player, "FRA");
+    assert_eq!(save.date, Hoi4Date::from_ymdh(1936, 1, 1, 12));
+    let fra = save.country(&"FRA".parse()?).unwrap();
+    assert!((fra.stability - 0.455).abs() < 1e-6);
+
+    let mut melted = Vec::new();
+    file.melt(MeltOptions::new(), &resolver, &mut melted)?;
+    let melted_text = std::str::from_utf8(&melted)?;
+    assert!(melted_text.contains("stability=0.455"));
+    assert!(melted_text.contains("war_support=1.25"));
+    assert!(melted_text.contains("ideology=fascism"));
+    assert!(melted_text.contains("start=1936.1.1.1"));
+    assert!(melted_text.contains("__unknown_0x3000=2"));
+
+    // Freezing the melted output yields the same binary
+    let mut refrozen = Vec::new();
+    Hoi4ParsedText::from_slice(&melted)?.freeze(&names, &mut refrozen)?;
+    assert_eq!(refrozen, binary);
+
+    // Newer saves store decimals as hundred-thousandths instead of
+    // thousandths
+    let text = b"HOI4txt\nsave_version=30\nstability=0.5\n";
+    let mut binary = Vec::new();
+    Hoi4ParsedText::from_slice(text)?.freeze(&names, &mut binary)?;
+    let (id, value) = binary[binary.len() - 10..].split_at(2);
+    assert_eq!(id, &0x000du16.to_le_bytes());
+    assert_eq!(value, &50000i64.to_le_bytes());
+
+    // Quoted dates are stored as integers like unquoted ones
+    let text = b"HOI4txt\ndate=\"1936.1.1.12\"\nplayer=\"FRA\"\n";
+    let mut binary = Vec::new();
+    Hoi4ParsedText::from_slice(text)?.freeze(&names, &mut binary)?;
+    let date = Hoi4Date::from_ymdh(1936, 1, 1, 12).to_binary();
+    assert_eq!(&binary[11..13], &0x000cu16.to_le_bytes());
+    assert_eq!(&binary[13..17], &date.to_le_bytes());
+    Ok(())
+}
+
+#[test]
+fn test_freeze_text_save() -> Result<(), Box<dyn Error>> {
+    let file_data = std::fs::read("assets/hoi4.txt").unwrap_or_default();
+    let tokens = TokenTable::from_text_lines(file_data.as_slice())?;
+    if tokens.is_empty() {
+        return Ok(());
+    }
+
+    let names: HashMap<&str, u16> = tokens.iter().map(|(id, name)| (name, id)).collect();
+    let data = utils::inflate(utils::request_file("1.10-normal-text.zip"));
+    let mut binary = Vec::new();
+    Hoi4ParsedText::from_slice(&data)?.freeze(&names, &mut binary)?;
+
+    let file = Hoi4File::from_slice(&binary)?;
+    assert_eq!(file.encoding(), Encoding::Binary);
+    let save = file.parse_save(&tokens)?;
+    assert_eq!(save.player, String::from("FRA"));
+    assert_eq!(save.date, Hoi4Date::from_ymdh(1936, 1, 1, 12));
+
+    let mut melted = Vec::new();
+    file.melt(MeltOptions::new(), &tokens, &mut melted)?;
+    let save = Hoi4File::from_slice(&melted)?.parse_save(&tokens)?;
+    assert_eq!(save.player, String::from("FRA"));
+    assert_eq!(save.date, Hoi4Date::from_ymdh(1936, 1, 1, 12));
+    Ok(())
+}
+
+#[test]
+fn test_write_text_roundtrip() -> Result<(), Box<dyn Error>> {
+    let data = br#"HOI4txt
+player="GER"
+date="1941.1.1.12"
+countries={
+	FRA={
+		stability=0.3
+		collaboration={
+			GER=0.65
+		}
+	}
+	GER={
+		stability=0.8
+		war_support=0.25
+		power_balance={
+			id="GER_army_navy_balance"
+			value=-0.35
+			left_side="army_side"
+			change=0.01
+		}
+		special_project={
+			id="sp_nuclear_reactor"
+			facility=64
+			scientist={
+				id=1204
+				type=73
+			}
+			progress=0.42
+			complete=yes
+		}
+	}
+	D01={
+		original_tag=FRA
+		cosmetic_tag="FRA_vichy"
+		color=rgb { 89 111 171 }
+		collaboration_government={
+			master=GER
+			date="1940.6.22.12"
+		}
+	}
+}
+civil_war={
+	original=SPR
+	revolter=D02
+	ideology="fascism"
+	date="1936.7.17.12"
+}
+"#;
+
+    let save = Hoi4File::from_slice(data)?.parse_save(&*TOKENS)?;
+    let mut out = Vec::new();
+    save.write_text(&mut out)?;
+
+    let text = std::str::from_utf8(&out)?;
+    assert!(text.starts_with("HOI4txt\nplayer=\"GER\"\ndate=1941.1.1.12\ncountries={\n\tFRA={"));
+    assert!(text.contains("\tD01={\n\t\tstability=0\n"));
+    assert!(text.contains("color={\n\t\t\t89 111 171\n\t\t}"));
+    assert!(text.contains("civil_war={\n\toriginal=\"SPR\"\n\trevolter=\"D02\"\n"));
+
+    let reparsed = Hoi4File::from_slice(&out)?.parse_save(&*TOKENS)?;
+    assert_eq!(format!("{:?}", reparsed), format!("{:?}", save));
+
+    // Only fields marked as dates are written as dates
+    #[derive(serde::Serialize)]
+    struct Note {
+        text: &'static str,
+    }
+
+    let mut out = Vec::new();
+    hoi4save::to_text_writer(
+        &mut out,
+        &Note {
+            text: "1936-01-01T11",
+        },
+    )?;
+    assert_eq!(out, b"text=\"1936-01-01T11\"\n");
+    Ok(())
+}
+
+#[test]
+fn test_text_editor() -> Result<(), Box<dyn Error>> {
+    let data = b"HOI4txt\nplayer=\"FRA\"\ndate=1936.1.1.12\ncountries={\n\tFRA={\n\t\tstability=0.5   # low\n\t\tvariables={ var_a=1 }\n\t}\n\tD01={ color=rgb { 89 111 171 } }\n}\nstates={\n\t64={ owner=\"FRA\" }\n\t64={ controller=\"GER\" }\n}\n";
+    let parsed = Hoi4ParsedText::from_slice(data)?;
+    let mut editor = parsed.editor();
+    assert!(editor.is_empty());
+    editor.set("countries.FRA.stability", "0.1")?;
+    editor.set("countries.FRA.stability", "0.9")?;
+    editor.set("countries.FRA.variables.var_a", "2.5")?;
+    editor.set("countries.D01.color.1", "0")?;
+    editor.set("states.64.owner", "GER")?;
+    editor.set("states.64.controller", "ITA")?;
+    editor.set("player", "GER")?;
+
+    let missing = editor.set("countries.GER.stability", "1").unwrap_err();
+    assert!(matches!(
+        missing.kind(),
+        hoi4save::Hoi4ErrorKind::PathNotFound { .. }
+    ));
+    assert!(editor.set("countries.FRA", "1").is_err());
+
+    // Unquoted replacements must be a single scalar
+    for value in ["", "0.5 b=1", "}", "0.5#"] {
+        let err = editor.set("countries.FRA.stability", value).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            hoi4save::Hoi4ErrorKind::InvalidEditValue { .. }
+        ));
+    }
+
+    // Quoted replacements are escaped
+    let mut quoted = parsed.editor();
+    quoted.set("player", r#"a "b""#)?;
+    let mut out = Vec::new();
+    quoted.write(&mut out)?;
+    assert!(out.starts_with(b"HOI4txt\nplayer=\"a \\\"b\\\"\"\n"));
+
+    let mut out = Vec::new();
+    editor.write(&mut out)?;
+    let expected = b"HOI4txt\nplayer=\"GER\"\ndate=1936.1.1.12\ncountries={\n\tFRA={\n\t\tstability=0.9   # low\n\t\tvariables={ var_a=2.5 }\n\t}\n\tD01={ color=rgb { 89 0 171 } }\n}\nstates={\n\t64={ owner=\"GER\" }\n\t64={ controller=\"ITA\" }\n}\n";
+    assert_eq!(std::str::from_utf8(&out)?, std::str::from_utf8(expected)?);
+
+    let save = Hoi4File::from_slice(&out)?.parse_save(&*TOKENS)?;
+    assert_eq!(save.player, "GER");
+    Ok(())
+}
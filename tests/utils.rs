@@ -1,3 +1,5 @@
+use hoi4save::{file::Hoi4ReaderFileKind, json::JsonOptions, Hoi4Error, Hoi4File};
+use std::collections::HashMap;
 use std::fs::File;
 use std::path::Path;
 use std::sync::{LazyLock, Mutex};
@@ -50,3 +52,149 @@ pub fn inflate(file: File) -> Vec<u8> {
     std::io::copy(&mut verifier, &mut output).unwrap();
     output
 }
+
+/// Token ids and names used to handcraft binary saves in tests
+pub const TEST_TOKENS: &[(u16, &str)] = &[
+    (0x2000, "player"),
+    (0x2001, "date"),
+    (0x2002, "save_version"),
+    (0x2003, "countries"),
+    (0x2004, "stability"),
+    (0x2005, "ironman"),
+    (0x2006, "version"),
+    (0x2007, "war_support"),
+    (0x2008, "seed"),
+    (0x2009, "start"),
+    (0x200a, "color"),
+    (0x0243, "rgb"),
+];
+
+/// A resolver for the ids in [`TEST_TOKENS`]
+pub fn test_resolver() -> HashMap<u16, &'static str> {
+    TEST_TOKENS.iter().copied().collect()
+}
+
+/// Streams a binary save to json through a reader file
+pub fn reader_json(
+    data: &[u8],
+    options: JsonOptions,
+    resolver: &HashMap<u16, &str>,
+) -> Result<String, Hoi4Error> {
+    let mut file = Hoi4File::from_reader(data)?;
+    let Hoi4ReaderFileKind::Binary(binary) = file.kind_mut() else {
+        panic!("expected binary file kind");
+    };
+    let mut out = Vec::new();
+    binary.json(options, resolver, &mut out)?;
+    Ok(String::from_utf8(out).expect("json to be utf-8"))
+}
+
+/// Writes the tokens of a handcrafted binary save
+pub struct BinaryBuilder(Vec<u8>);
+
+impl Default for BinaryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BinaryBuilder {
+    pub fn new() -> Self {
+        BinaryBuilder(b"HOI4bin".to_vec())
+    }
+
+    pub fn token(mut self, id: u16) -> Self {
+        self.0.extend_from_slice(&id.to_le_bytes());
+        self
+    }
+
+    /// A key followed by the equal operator
+    pub fn key(self, id: u16) -> Self {
+        self.token(id).token(0x0001)
+    }
+
+    pub fn open(self) -> Self {
+        self.token(0x0003)
+    }
+
+    pub fn close(self) -> Self {
+        self.token(0x0004)
+    }
+
+    pub fn i32(mut self, x: i32) -> Self {
+        self = self.token(0x000c);
+        self.0.extend_from_slice(&x.to_le_bytes());
+        self
+    }
+
+    pub fn string(mut self, data: &[u8]) -> Self {
+        self = self.token(0x000f);
+        self.0.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        self.0.extend_from_slice(data);
+        self
+    }
+
+    /// A `0x000d` decimal as written before save version 30: an i32 of
+    /// thousandths
+    pub fn decimal_i32(mut self, x: i32) -> Self {
+        self = self.token(0x000d);
+        self.0.extend_from_slice(&x.to_le_bytes());
+        self
+    }
+
+    /// A `0x000d` decimal as written from save version 30: an i64 of
+    /// hundred thousandths
+    pub fn decimal_i64(mut self, x: i64) -> Self {
+        self = self.token(0x000d);
+        self.0.extend_from_slice(&x.to_le_bytes());
+        self
+    }
+
+    /// A `0x0167` decimal: an i64 fixed point number with 15 fractional bits
+    pub fn f64(mut self, x: i64) -> Self {
+        self = self.token(0x0167);
+        self.0.extend_from_slice(&x.to_le_bytes());
+        self
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/// A minimal binary save written with the ids from [`TEST_TOKENS`]
+pub fn test_binary_save() -> Vec<u8> {
+    BinaryBuilder::new()
+        .key(0x2000)
+        .string(b"FRA")
+        .key(0x2005)
+        .i32(1)
+        .key(0x2001)
+        .i32(60759371)
+        .key(0x2002)
+        .i32(30)
+        .key(0x2003)
+        .open()
+        .string(b"FRA")
+        .token(0x0001)
+        .open()
+        .key(0x2004)
+        .decimal_i64(100000)
+        .close()
+        .close()
+        .build()
+}
+
+/// Hands out a single byte per read to exercise buffer refills
+pub struct Trickle<'a>(pub &'a [u8]);
+
+impl std::io::Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some((first, rest)) = self.0.split_first() else {
+            return Ok(0);
+        };
+        buf[0] = *first;
+        self.0 = rest;
+        Ok(1)
+    }
+}